@@ -0,0 +1,51 @@
+//! Compares printing a few lines at the end of a large file with and without
+//! highlighting checkpoints.
+//!
+//! Run
+//! ```
+//! cargo run --release --example checkpoints > /dev/null
+//! ```
+
+use std::env;
+use std::fs;
+use std::time::Instant;
+
+use prettyprint::{LineRange, LineRanges, PagingMode, PrettyPrintError, PrettyPrinter};
+
+const FUNCTIONS: usize = 10_000;
+
+fn main() -> Result<(), PrettyPrintError> {
+    let mut fixture = String::new();
+    for i in 0..FUNCTIONS {
+        fixture.push_str(&format!(
+            "/// Returns {i} squared\nfn square_{i}() -> usize {{\n    let x = {i};\n    x * x\n}}\n\n"
+        ));
+    }
+    let path = env::temp_dir().join("prettyprint-checkpoints.rs");
+    fs::write(&path, &fixture)?;
+    let path = path.to_string_lossy().into_owned();
+
+    let lines = fixture.lines().count();
+    let range = LineRange::from(&format!("{}:+10", lines - 20))?;
+
+    for &checkpoints in &[false, true] {
+        let printer = PrettyPrinter::default()
+            .paging_mode(PagingMode::Never)
+            .line_ranges(LineRanges::from(vec![range.clone()]))
+            .highlight_checkpoints(checkpoints)
+            .build()?;
+
+        for run in 1..=3 {
+            let start = Instant::now();
+            printer.file(path.as_str())?;
+            eprintln!(
+                "checkpoints: {:5}, run {}: {:?}",
+                checkpoints,
+                run,
+                start.elapsed()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -6,13 +6,20 @@ use syntect::dumps::{from_binary, from_reader};
 use syntect::highlighting::{Theme, ThemeSet};
 use syntect::parsing::{SyntaxReference, SyntaxSet};
 
+use crate::detection::{candidate_lines, detect_modeline, detect_shebang};
 use crate::dirs::PROJECT_DIRS;
 use crate::errors::*;
+#[cfg(feature = "guess")]
+use crate::guess::{guess_language, MIN_CONFIDENCE};
 use crate::inputfile::{InputFile, InputFileReader};
-use crate::syntax_mapping::SyntaxMapping;
+use crate::syntax_mapping::{MappingTarget, SyntaxMapping};
 
 pub const PRETTYPRINT_THEME_DEFAULT: &str = "Monokai Extended";
 
+/// Number of bytes at the start of a file that are used to guess its language.
+#[cfg(feature = "guess")]
+const GUESS_SAMPLE_BYTES: usize = 16 * 1024;
+
 pub struct HighlightingAssets {
     pub syntax_set: SyntaxSet,
     pub theme_set: ThemeSet,
@@ -88,41 +95,119 @@ impl HighlightingAssets {
         filename: &InputFile,
         reader: &mut InputFileReader,
         mapping: &SyntaxMapping,
+        language_guessing: bool,
     ) -> &SyntaxReference {
-        let syntax = match (language, filename) {
-            (Some(language), _) => self.syntax_set.find_syntax_by_token(&language),
-            (None, InputFile::Ordinary(filename)) => {
+        if let Some(language) = language {
+            return self
+                .syntax_set
+                .find_syntax_by_token(&language)
+                .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        }
+
+        // Modelines take precedence over the file name, since they are an
+        // explicit declaration of the file type. Shebangs do not.
+        let lines = candidate_lines(filename, &reader.first_line);
+        let modeline_syntax = || detect_modeline(&lines).and_then(|t| self.find_syntax(&t));
+        let shebang_syntax = || detect_shebang(&lines).and_then(|t| self.find_syntax(&t));
+        let line_syntax = || {
+            String::from_utf8(reader.first_line.clone())
+                .ok()
+                .and_then(|l| self.syntax_set.find_syntax_by_first_line(&l))
+        };
+        let content_syntax = || {
+            if language_guessing {
+                self.get_content_syntax(&reader.text_sample())
+            } else {
+                None
+            }
+        };
+
+        let syntax = match filename {
+            InputFile::Ordinary(filename) => {
                 let path = Path::new(&filename);
-                let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
-
-                let file_name = mapping.replace(file_name);
-                let extension = mapping.replace(extension);
-
-                let ext_syntax = self
-                    .syntax_set
-                    .find_syntax_by_extension(&file_name)
-                    .or_else(|| self.syntax_set.find_syntax_by_extension(&extension));
-                let line_syntax = if ext_syntax.is_none() {
-                    String::from_utf8(reader.first_line.clone())
+                // Symlinks are mapped by their own name, and only by the name
+                // of their target if no rule applies to them.
+                let path_syntax = || match self.get_path_syntax(path, mapping) {
+                    None if mapping.get_syntax_for(path).is_none() => path
+                        .canonicalize()
                         .ok()
-                        .and_then(|l| self.syntax_set.find_syntax_by_first_line(&l))
-                } else {
-                    None
+                        .filter(|canonical| canonical != path)
+                        .and_then(|canonical| self.get_path_syntax(&canonical, mapping)),
+                    syntax => syntax,
                 };
-                let syntax = ext_syntax.or(line_syntax);
-                syntax
+
+                modeline_syntax()
+                    .or_else(path_syntax)
+                    .or_else(shebang_syntax)
+                    .or_else(line_syntax)
+                    .or_else(content_syntax)
             }
-            (None, InputFile::StdIn) => String::from_utf8(reader.first_line.clone())
-                .ok()
-                .and_then(|l| self.syntax_set.find_syntax_by_first_line(&l)),
-            (None, InputFile::String(_)) => String::from_utf8(reader.first_line.clone())
-                .ok()
-                .and_then(|l| self.syntax_set.find_syntax_by_first_line(&l)),
+            InputFile::StdIn | InputFile::String(_) => modeline_syntax()
+                .or_else(shebang_syntax)
+                .or_else(line_syntax)
+                .or_else(content_syntax),
         };
 
         syntax.unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
     }
+
+    /// Finds a syntax by token (e.g. a file extension) or by its name.
+    fn find_syntax(&self, token: &str) -> Option<&SyntaxReference> {
+        self.syntax_set
+            .find_syntax_by_token(token)
+            .or_else(|| self.syntax_set.find_syntax_by_name(token))
+    }
+
+    /// Guesses the syntax from the beginning of the decoded input.
+    #[cfg(feature = "guess")]
+    fn get_content_syntax(&self, sample: &str) -> Option<&SyntaxReference> {
+        let mut end = sample.len().min(GUESS_SAMPLE_BYTES);
+        while !sample.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        guess_language(&sample[..end])
+            .filter(|guess| guess.confidence >= MIN_CONFIDENCE)
+            .and_then(|guess| self.find_syntax(&guess.language))
+    }
+
+    #[cfg(not(feature = "guess"))]
+    fn get_content_syntax(&self, _sample: &str) -> Option<&SyntaxReference> {
+        None
+    }
+
+    /// Determines the syntax from the path of a file, consulting the syntax
+    /// mapping first and retrying without ignored suffixes like `.bak`.
+    /// The file itself is never read.
+    pub fn get_path_syntax(
+        &self,
+        path: &Path,
+        mapping: &SyntaxMapping,
+    ) -> Option<&SyntaxReference> {
+        match mapping.get_syntax_for(path) {
+            Some(MappingTarget::MapToUnknown) => return None,
+            Some(MappingTarget::MapTo(syntax)) => {
+                if let Some(syntax) = self.find_syntax(syntax) {
+                    return Some(syntax);
+                }
+            }
+            None => {}
+        }
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+
+        self.syntax_set
+            .find_syntax_by_extension(file_name)
+            .or_else(|| self.syntax_set.find_syntax_by_extension(extension))
+            .or_else(|| {
+                mapping
+                    .strip_ignored_suffix(file_name)
+                    .and_then(|stripped| {
+                        self.get_path_syntax(&path.with_file_name(stripped), mapping)
+                    })
+            })
+    }
 }
 
 fn theme_set_path() -> PathBuf {
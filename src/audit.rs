@@ -0,0 +1,231 @@
+use std::ops::Range;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref IDENTIFIER: Regex = Regex::new(r"[\p{L}\p{M}\p{N}_]+").unwrap();
+    static ref LATIN: Regex = Regex::new(r"^\p{Latin}$").unwrap();
+    static ref GREEK: Regex = Regex::new(r"^\p{Greek}$").unwrap();
+    static ref CYRILLIC: Regex = Regex::new(r"^\p{Cyrillic}$").unwrap();
+    static ref ARMENIAN: Regex = Regex::new(r"^\p{Armenian}$").unwrap();
+}
+
+/// Letters of other scripts which look like Latin letters, with the Latin
+/// letter they are confused with.
+const CONFUSABLES: &[(char, char)] = &[
+    ('а', 'a'),
+    ('в', 'B'),
+    ('е', 'e'),
+    ('к', 'k'),
+    ('м', 'M'),
+    ('н', 'H'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('т', 'T'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('ѕ', 's'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ԁ', 'd'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('А', 'A'),
+    ('В', 'B'),
+    ('Е', 'E'),
+    ('К', 'K'),
+    ('М', 'M'),
+    ('Н', 'H'),
+    ('О', 'O'),
+    ('Р', 'P'),
+    ('С', 'C'),
+    ('Т', 'T'),
+    ('Х', 'X'),
+    ('Ѕ', 'S'),
+    ('І', 'I'),
+    ('Ј', 'J'),
+    ('α', 'a'),
+    ('ο', 'o'),
+    ('ν', 'v'),
+    ('ρ', 'p'),
+    ('Α', 'A'),
+    ('Β', 'B'),
+    ('Ε', 'E'),
+    ('Ζ', 'Z'),
+    ('Η', 'H'),
+    ('Ι', 'I'),
+    ('Κ', 'K'),
+    ('Μ', 'M'),
+    ('Ν', 'N'),
+    ('Ο', 'O'),
+    ('Ρ', 'P'),
+    ('Τ', 'T'),
+    ('Υ', 'Y'),
+    ('Χ', 'X'),
+    ('օ', 'o'),
+    ('ս', 'u'),
+    ('հ', 'h'),
+];
+
+/// What kind of suspicious content was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FindingKind {
+    /// A bidirectional control character, which can reorder how the code is
+    /// displayed ("trojan source")
+    BidiControl,
+    /// A letter from another script than the rest of its identifier
+    MixedScript,
+    /// A letter from another script which looks like a letter of the script
+    /// of its identifier, like a Cyrillic `а` in a Latin identifier
+    Homoglyph,
+}
+
+/// Suspicious content found while auditing an input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub line: usize,
+    /// The column of the character, counted in characters from 1
+    pub column: usize,
+    pub character: char,
+    pub kind: FindingKind,
+}
+
+/// The scripts whose letters are easily confused with each other.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+}
+
+fn script(c: char) -> Option<Script> {
+    if c.is_ascii() {
+        return Some(Script::Latin).filter(|_| c.is_ascii_alphabetic());
+    }
+
+    let mut buffer = [0; 4];
+    let c: &str = c.encode_utf8(&mut buffer);
+    if LATIN.is_match(c) {
+        Some(Script::Latin)
+    } else if GREEK.is_match(c) {
+        Some(Script::Greek)
+    } else if CYRILLIC.is_match(c) {
+        Some(Script::Cyrillic)
+    } else if ARMENIAN.is_match(c) {
+        Some(Script::Armenian)
+    } else {
+        None
+    }
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// Finds suspicious content in a line. Returns the byte ranges of the
+/// offending characters, sorted by their position.
+pub fn audit_line(line: &str) -> Vec<(Range<usize>, FindingKind)> {
+    if line.is_ascii() {
+        return vec![];
+    }
+
+    let mut findings: Vec<_> = line
+        .char_indices()
+        .filter(|&(_, c)| is_bidi_control(c))
+        .map(|(index, c)| (index..index + c.len_utf8(), FindingKind::BidiControl))
+        .collect();
+
+    for identifier in IDENTIFIER.find_iter(line) {
+        findings.extend(
+            audit_identifier(identifier.as_str())
+                .into_iter()
+                .map(|(range, kind)| {
+                    let start = identifier.start();
+                    (start + range.start..start + range.end, kind)
+                }),
+        );
+    }
+
+    findings.sort_by_key(|(range, _)| range.start);
+    findings
+}
+
+/// Finds the letters of an identifier which don't belong to the script of
+/// most of its letters.
+fn audit_identifier(identifier: &str) -> Vec<(Range<usize>, FindingKind)> {
+    let letters: Vec<_> = identifier
+        .char_indices()
+        .filter_map(|(index, c)| script(c).map(|script| (index, c, script)))
+        .collect();
+
+    // Ties go to the first script, so that Latin identifiers win.
+    let mut counts: Vec<(Script, usize)> = vec![];
+    for &(_, _, script) in &letters {
+        match counts.iter_mut().find(|(known, _)| *known == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    if counts.len() < 2 {
+        return vec![];
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.partial_cmp(&b.0).unwrap()));
+    let dominant = counts[0].0;
+
+    letters
+        .into_iter()
+        .filter(|&(_, _, script)| script != dominant)
+        .map(|(index, c, _)| {
+            let looks_alike = CONFUSABLES.iter().any(|&(other, latin)| {
+                (c == other && dominant == Script::Latin)
+                    || (c == latin && script(other) == Some(dominant))
+            });
+            let kind = if looks_alike {
+                FindingKind::Homoglyph
+            } else {
+                FindingKind::MixedScript
+            };
+            (index..index + c.len_utf8(), kind)
+        })
+        .collect()
+}
+
+#[test]
+fn test_bidi_controls() {
+    // The classic "trojan source" comment which hides the rest of the line
+    let line = "/*\u{202E} } \u{2066}if (isAdmin)\u{2069} \u{2066} begin admins only */\n";
+    let findings = audit_line(line);
+    assert_eq!(4, findings.len());
+    assert!(findings
+        .iter()
+        .all(|(_, kind)| *kind == FindingKind::BidiControl));
+    assert_eq!(2..5, findings[0].0);
+}
+
+#[test]
+fn test_homoglyphs() {
+    assert_eq!(
+        vec![(1..3, FindingKind::Homoglyph)],
+        audit_line("pаssword\n")
+    );
+    // A Latin letter in a Cyrillic identifier
+    assert_eq!(vec![(2..3, FindingKind::Homoglyph)], audit_line("пaроль"));
+    assert_eq!(
+        vec![(4..6, FindingKind::MixedScript)],
+        audit_line("let λx = 1;")
+    );
+    assert_eq!(vec![(4..6, FindingKind::MixedScript)], audit_line("userд"));
+}
+
+#[test]
+fn test_harmless_text() {
+    assert!(audit_line("let x = \"naïve café\";\n").is_empty());
+    assert!(audit_line("// Привет, мир\n").is_empty());
+    assert!(audit_line("let 名前 = name_2;").is_empty());
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::errors::*;
+
+/// The number of worker threads used if none is configured.
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+/// Renders `inputs` concurrently on up to `threads` worker threads and passes
+/// the results to `write` in the order of the inputs, as soon as all previous
+/// ones are written. Each worker creates its state with `init` before it
+/// renders its first input.
+///
+/// Rendering stops at the first error. Results of the inputs before it are
+/// still written, then the error is returned.
+pub fn render_ordered<I, S, R, N, F, W>(
+    inputs: &[I],
+    threads: usize,
+    init: N,
+    render: F,
+    mut write: W,
+) -> Result<()>
+where
+    I: Sync,
+    R: Send,
+    N: Fn() -> Result<S> + Sync,
+    F: Fn(&mut S, &I) -> Result<R> + Sync,
+    W: FnMut(R) -> Result<()>,
+{
+    let threads = threads.clamp(1, inputs.len().max(1));
+    let next = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (next, stopped, init, render) = (&next, &stopped, &init, &render);
+            scope.spawn(move || {
+                let mut state = None;
+                // Every claimed input is rendered and sent, so that no input
+                // before an error goes missing.
+                while !stopped.load(Ordering::SeqCst) {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    if index >= inputs.len() {
+                        break;
+                    }
+                    let result = match state {
+                        Some(ref mut state) => render(state, &inputs[index]),
+                        None => init().and_then(|s| render(state.insert(s), &inputs[index])),
+                    };
+                    if result.is_err() {
+                        stopped.store(true, Ordering::SeqCst);
+                    }
+                    if sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // Only the workers hold senders now, so receiving ends once all of
+        // them are done.
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut written = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&written) {
+                let written_ok = result.and_then(&mut write);
+                if written_ok.is_err() {
+                    stopped.store(true, Ordering::SeqCst);
+                    return written_ok;
+                }
+                written += 1;
+            }
+        }
+
+        // Results are only left over if an input before them failed.
+        pending
+            .into_values()
+            .find_map(Result::err)
+            .map_or(Ok(()), Err)
+    })
+}
+
+#[test]
+fn test_render_ordered() {
+    use std::time::Duration;
+
+    let inputs: Vec<u64> = (0..20).collect();
+    let mut output = Vec::new();
+    render_ordered(
+        &inputs,
+        4,
+        || Ok(()),
+        |_, &i| {
+            // Later inputs finish first.
+            thread::sleep(Duration::from_millis(20 - i));
+            Ok(i * 2)
+        },
+        |r| {
+            output.push(r);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!((0..20).map(|i| i * 2).collect::<Vec<_>>(), output);
+}
+
+#[test]
+fn test_render_ordered_stops_at_first_error() {
+    let inputs: Vec<usize> = (0..10).collect();
+    let mut output = Vec::new();
+    let result = render_ordered(
+        &inputs,
+        1,
+        || Ok(()),
+        |_, &i| {
+            if i == 3 {
+                Err(format!("input {} failed", i).into())
+            } else {
+                Ok(i)
+            }
+        },
+        |r| {
+            output.push(r);
+            Ok(())
+        },
+    );
+
+    assert_eq!("input 3 failed", result.unwrap_err().to_string());
+    assert_eq!(vec![0, 1, 2], output);
+}
+
+#[test]
+fn test_render_ordered_returns_error_of_concurrent_input() {
+    use std::time::Duration;
+
+    let inputs: Vec<u64> = (0..40).collect();
+    let mut output = Vec::new();
+    let result = render_ordered(
+        &inputs,
+        8,
+        || Ok(()),
+        |_, &i| {
+            // The failing input finishes last among the first few.
+            if i == 5 {
+                thread::sleep(Duration::from_millis(50));
+                Err(format!("input {} failed", i).into())
+            } else {
+                Ok(i)
+            }
+        },
+        |r| {
+            output.push(r);
+            Ok(())
+        },
+    );
+
+    assert_eq!("input 5 failed", result.unwrap_err().to_string());
+    assert_eq!(vec![0, 1, 2, 3, 4], output);
+}
+
+#[test]
+fn test_render_ordered_without_inputs() {
+    let inputs: Vec<usize> = vec![];
+    render_ordered(
+        &inputs,
+        0,
+        || Ok(()),
+        |_, &i| Ok(i),
+        |_| panic!("nothing to write"),
+    )
+    .unwrap();
+}
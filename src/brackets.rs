@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+use syntect::parsing::{ScopeStack, ScopeStackOp};
+
+use crate::scopes::{in_string_or_comment, is_invalid, is_punctuation};
+
+/// Matches the brackets of an input line by line, to color them by their
+/// depth of nesting. Only brackets which are punctuation count, i.e. not
+/// those in strings and comments, and those the syntax marks as invalid.
+#[derive(Debug, Default)]
+pub struct BracketMatcher {
+    /// The brackets which are still open
+    open: Vec<char>,
+    /// Whether the syntax scopes brackets as punctuation at all
+    scoped: bool,
+}
+
+impl BracketMatcher {
+    /// Finds the brackets of a line, from the scopes at its beginning and the
+    /// operations of the parser for it. Returns their byte ranges with their
+    /// depth, or `None` if a closing bracket doesn't match the open bracket.
+    ///
+    /// Brackets which are never closed can't be told apart when they are
+    /// printed, so they get a depth like the others.
+    pub fn match_line(
+        &mut self,
+        line: &str,
+        start: &ScopeStack,
+        ops: &[(usize, ScopeStackOp)],
+    ) -> Vec<(Range<usize>, Option<usize>)> {
+        let mut stack = start.clone();
+        let mut ops = ops.iter().peekable();
+        let mut brackets = vec![];
+
+        for (index, c) in line.char_indices() {
+            while let Some((_, op)) = ops.next_if(|(position, _)| *position <= index) {
+                stack.apply(op).ok();
+            }
+            if !"()[]{}".contains(c) || in_string_or_comment(&stack) {
+                continue;
+            }
+
+            // The syntax knows about stray brackets, e.g. in C. Others leave
+            // them out of any scope when nothing is open, e.g. Rust.
+            let range = index..index + 1;
+            let closing = ")]}".contains(c);
+            if is_invalid(&stack) {
+                brackets.push((range, None));
+            } else if !is_punctuation(&stack) {
+                if self.scoped && closing && self.open.is_empty() {
+                    brackets.push((range, None));
+                }
+            } else if !closing {
+                self.scoped = true;
+                brackets.push((range, Some(self.open.len())));
+                self.open.push(c);
+            } else if self.open.last() == Some(&opening(c)) {
+                self.scoped = true;
+                self.open.pop();
+                brackets.push((range, Some(self.open.len())));
+            } else {
+                brackets.push((range, None));
+            }
+        }
+
+        brackets
+    }
+}
+
+fn opening(closing: char) -> char {
+    match closing {
+        ')' => '(',
+        ']' => '[',
+        _ => '{',
+    }
+}
+
+/// Parses lines with a fake parser which makes every bracket punctuation,
+/// and quotes strings.
+#[cfg(test)]
+fn match_lines(lines: &[&str]) -> Vec<Vec<(Range<usize>, Option<usize>)>> {
+    use syntect::parsing::Scope;
+
+    let punctuation = Scope::new("punctuation.section").unwrap();
+    let string = Scope::new("string.quoted").unwrap();
+
+    let mut matcher = BracketMatcher::default();
+    let start = ScopeStack::new();
+    lines
+        .iter()
+        .map(|line| {
+            let mut ops = vec![];
+            let mut in_string = false;
+            for (index, c) in line.char_indices() {
+                if c == '"' {
+                    in_string = !in_string;
+                    ops.push(if in_string {
+                        (index, ScopeStackOp::Push(string))
+                    } else {
+                        (index + 1, ScopeStackOp::Pop(1))
+                    });
+                } else if "()[]{}".contains(c) {
+                    ops.push((index, ScopeStackOp::Push(punctuation)));
+                    ops.push((index + 1, ScopeStackOp::Pop(1)));
+                }
+            }
+            matcher.match_line(line, &start, &ops)
+        })
+        .collect()
+}
+
+#[test]
+fn test_match_line() {
+    let brackets = match_lines(&["f(a[0], { b })"]);
+    let depths: Vec<_> = brackets[0].iter().map(|(_, depth)| *depth).collect();
+    assert_eq!(
+        vec![Some(0), Some(1), Some(1), Some(1), Some(1), Some(0)],
+        depths
+    );
+    assert_eq!(1..2, brackets[0][0].0);
+}
+
+#[test]
+fn test_match_across_lines() {
+    let brackets = match_lines(&["fn f() {", "    g(\"(\");", "}"]);
+    assert_eq!(
+        vec![
+            vec![(4..5, Some(0)), (5..6, Some(0)), (7..8, Some(0))],
+            vec![(5..6, Some(1)), (9..10, Some(1))],
+            vec![(0..1, Some(0))],
+        ],
+        brackets
+    );
+}
+
+#[test]
+fn test_stray_brackets() {
+    use syntect::parsing::Scope;
+
+    let stray = Scope::new("invalid.illegal.stray-bracket-end").unwrap();
+    let ops = vec![(0, ScopeStackOp::Push(stray)), (1, ScopeStackOp::Pop(1))];
+    assert_eq!(
+        vec![(0..1, None)],
+        BracketMatcher::default().match_line("}", &ScopeStack::new(), &ops)
+    );
+}
+
+#[test]
+fn test_unscoped_brackets() {
+    let mut matcher = BracketMatcher::default();
+    let start = ScopeStack::new();
+
+    // Brackets are ignored if the syntax doesn't scope them, ...
+    assert!(matcher.match_line("f(x) }", &start, &[]).is_empty());
+
+    // ... otherwise one which isn't scoped and closes nothing is stray.
+    let punctuation = syntect::parsing::Scope::new("punctuation.section").unwrap();
+    let ops = vec![
+        (1, ScopeStackOp::Push(punctuation)),
+        (2, ScopeStackOp::Pop(1)),
+        (2, ScopeStackOp::Push(punctuation)),
+        (3, ScopeStackOp::Pop(1)),
+    ];
+    assert_eq!(2, matcher.match_line("f()", &start, &ops).len());
+    assert_eq!(vec![(0..1, None)], matcher.match_line("}", &start, &[]));
+}
+
+#[test]
+fn test_unbalanced() {
+    let brackets = match_lines(&["(]", ")"]);
+    assert_eq!(
+        vec![vec![(0..1, Some(0)), (1..2, None)], vec![(0..1, Some(0))]],
+        brackets
+    );
+}
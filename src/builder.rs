@@ -1,16 +1,38 @@
+use std::any::type_name;
 use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::io::Write;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 
 use console::Term;
+use regex::Regex;
+#[cfg(feature = "serde")]
+use serde::Serialize;
 use syntect::{dumps::from_binary, highlighting::Theme};
 
 use crate::assets::{HighlightingAssets, PRETTYPRINT_THEME_DEFAULT};
+use crate::batch::{default_threads, render_ordered};
+use crate::charset;
+use crate::checkpoint::{cache_key, CheckpointCache, Recorder};
+use crate::diff::{compute_diff, visible_lines, DiffBlock, DiffLine, DiffMode, LineChange};
+use crate::embed::Embedding;
 use crate::errors::*;
+use crate::follow::{FileChange, FileWatcher, FollowHandle};
+use crate::hexdump::{BinaryMode, RowReader};
 use crate::inputfile::{InputFile, InputFileReader};
+use crate::line_ending::{split_line_ending, LineEndingMode};
 use crate::line_range::RangeCheckResult;
 use crate::output::OutputType;
-use crate::printer::{InteractivePrinter, Printer};
+use crate::printer::{InteractivePrinter, Printer, PrinterOptions};
+#[cfg(feature = "reformat")]
+use crate::reformat::{reformat, DataFormat};
+use crate::safeguard::{Budget, LineSafeguards, LongLines, PrintReport};
+use crate::search::{build_pattern, find_matches, ContextFilter, FilterAction};
+use crate::selector::Selector;
 
 #[cfg(windows)]
 use ansi_term;
@@ -18,6 +40,9 @@ use ansi_term;
 use crate::line_range::LineRanges;
 use crate::style::{OutputComponent, OutputComponents, OutputWrap};
 use crate::syntax_mapping::SyntaxMapping;
+#[cfg(feature = "serde")]
+use crate::value::Format;
+use crate::whitespace::IndentGuides;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PagingMode {
@@ -50,6 +75,34 @@ pub struct PrettyPrint {
     #[builder(default = "false")]
     show_nonprintable: bool,
 
+    /// Whether or not to replace suspicious invisible characters, like
+    /// bidirectional controls and zero-width spaces, even if
+    /// `show_nonprintable` is off
+    #[builder(default = "false")]
+    flag_nonprintable: bool,
+
+    /// Whether or not to warn about content which may hide what the code
+    /// does, like bidirectional controls and homoglyphs. The findings are
+    /// part of the report.
+    #[builder(default = "false")]
+    audit: bool,
+
+    /// Mark trailing whitespace, and indentation which mixes tabs and spaces
+    /// against the style of the input
+    #[builder(default = "false")]
+    whitespace_lint: bool,
+
+    /// Draw guides at the levels of indentation. The width of a level is
+    /// detected for each input, or the tab width for inputs indented with
+    /// tabs.
+    #[builder(default = "IndentGuides::Off")]
+    indent_guides: IndentGuides,
+
+    /// Color brackets by their depth of nesting, except in strings and
+    /// comments. Brackets which don't match get an error style.
+    #[builder(default = "false")]
+    rainbow_brackets: bool,
+
     /// The character width of the terminal
     #[builder(default = "Term::stdout().size().1 as usize")]
     term_width: usize,
@@ -100,8 +153,8 @@ pub struct PrettyPrint {
     #[builder(default = "String::from(PRETTYPRINT_THEME_DEFAULT)")]
     theme: String,
 
-    /// File extension/name mappings
-    #[builder(default)]
+    /// Glob based mappings from file paths to syntaxes
+    #[builder(default = "SyntaxMapping::builtin()")]
     syntax_mapping: SyntaxMapping,
 
     /// Load custom syntax-highlighter
@@ -119,6 +172,154 @@ pub struct PrettyPrint {
     /// Whether to print some characters using italics
     #[builder(default = "false")]
     use_italic_text: bool,
+
+    /// Guess the language from the content if it can't be detected otherwise.
+    /// This requires the `guess` feature.
+    #[builder(default = "true")]
+    language_guessing: bool,
+
+    /// Reformat structured data (JSON, YAML, TOML and XML) before printing.
+    /// This requires the `reformat` feature.
+    #[builder(default = "false")]
+    reformat: bool,
+
+    /// The indentation width used when reformatting structured data
+    #[builder(default = "2")]
+    reformat_indent: usize,
+
+    /// Whether to sort keys when reformatting structured data
+    #[builder(default = "false")]
+    sort_keys: bool,
+
+    /// How diffs are laid out
+    #[builder(default = "DiffMode::Unified")]
+    diff_mode: DiffMode,
+
+    /// Number of unchanged lines shown around changes in a diff.
+    /// `None` shows all unchanged lines.
+    #[builder(default = "None")]
+    diff_context: Option<usize>,
+
+    /// Whether to highlight the changed words within changed lines
+    #[builder(default = "false")]
+    word_diff: bool,
+
+    /// Highlight the content of patches in the language of the patched files
+    #[builder(default = "true")]
+    highlight_patches: bool,
+
+    /// Highlight timestamps, levels, key-value pairs and source locations of
+    /// logs (plain text files named `*.log` or starting with a timestamp, or
+    /// with the language `log`)
+    #[builder(default = "true")]
+    highlight_logs: bool,
+
+    /// Highlight JSON records and payloads in logs with the JSON syntax
+    #[builder(default = "true")]
+    highlight_log_json: bool,
+
+    /// Blocks of other languages which are highlighted in their own syntax,
+    /// e.g. code fences in Markdown
+    #[builder(default = "Embedding::builtin()")]
+    embeddings: Vec<Embedding>,
+
+    /// The encoding of the inputs, as a WHATWG label like `latin1` or
+    /// `shift_jis`. Overrides the detected encoding.
+    #[builder(default = "None")]
+    encoding: Option<String>,
+
+    /// Guess the encoding of text inputs which aren't valid UTF-8, instead
+    /// of replacing the invalid bytes
+    #[builder(default = "false")]
+    detect_encoding: bool,
+
+    /// How the line endings of the inputs are printed
+    #[builder(default = "LineEndingMode::Keep")]
+    line_endings: LineEndingMode,
+
+    /// Mark the end of inputs whose last line doesn't end with a newline
+    #[builder(default = "false")]
+    eof_marker: bool,
+
+    /// How binary inputs are printed
+    #[builder(default = "BinaryMode::Skip")]
+    binary_mode: BinaryMode,
+
+    /// The number of bytes in each row of a hex dump
+    #[builder(default = "16")]
+    hex_bytes_per_row: usize,
+
+    /// Highlight all matches of this regular expression
+    #[builder(default = "None")]
+    highlight_pattern: Option<Regex>,
+
+    /// Highlight all occurrences of these strings
+    #[builder(default = "vec![]")]
+    highlight_literals: Vec<String>,
+
+    /// Only print lines with highlighted matches, and this many lines of
+    /// context around them. `None` prints all lines.
+    #[builder(default = "None")]
+    match_context: Option<usize>,
+
+    /// Print only the lines of these code structures (e.g. functions), in
+    /// addition to the line ranges
+    #[builder(default = "vec![]")]
+    selectors: Vec<Selector>,
+
+    /// Remember the state of the highlighter at regular intervals, so that
+    /// printing later lines of the same input again skips most of the work
+    #[builder(default = "false")]
+    highlight_checkpoints: bool,
+
+    /// The number of lines between two checkpoints
+    #[builder(default = "1000")]
+    checkpoint_interval: usize,
+
+    /// Additionally store the lines at which highlighting can restart in the
+    /// cache directory, so that they are used by later runs, too
+    #[builder(default = "false")]
+    persist_checkpoints: bool,
+
+    // Highlighter states can't be sent to other threads, so they are kept
+    // out of the builder.
+    #[builder(setter(custom), field(type = "()", build = "Default::default()"))]
+    #[builder_field_attr(allow(dead_code))]
+    checkpoints: Mutex<CheckpointCache>,
+
+    /// Lines longer than this (in bytes) are not highlighted
+    #[builder(default = "None")]
+    max_line_length: Option<usize>,
+
+    /// Whether lines longer than `max_line_length` are printed plain or
+    /// truncated
+    #[builder(default = "LongLines::Plain")]
+    long_lines: LongLines,
+
+    /// Turn off highlighting for the rest of the input once a single line
+    /// takes longer than this to highlight
+    #[builder(default = "None")]
+    highlight_timeout: Option<Duration>,
+
+    /// The maximum number of bytes printed per input
+    #[builder(default = "None")]
+    max_input_bytes: Option<usize>,
+
+    /// The maximum number of lines printed per input
+    #[builder(default = "None")]
+    max_input_lines: Option<usize>,
+
+    #[builder(setter(skip))]
+    report: Mutex<PrintReport>,
+
+    /// The number of threads used to highlight files printed with `files`.
+    /// Defaults to the number of available cores.
+    #[builder(default = "None")]
+    threads: Option<usize>,
+
+    /// How often a followed file is checked for new lines
+    #[builder(default = "Duration::from_millis(250)")]
+    follow_interval: Duration,
 }
 
 impl From<&PrettyPrint> for PrettyPrinter {
@@ -126,6 +327,11 @@ impl From<&PrettyPrint> for PrettyPrinter {
         PrettyPrinter::default()
             .language(printer.language.clone())
             .show_nonprintable(printer.show_nonprintable)
+            .flag_nonprintable(printer.flag_nonprintable)
+            .audit(printer.audit)
+            .whitespace_lint(printer.whitespace_lint)
+            .indent_guides(printer.indent_guides)
+            .rainbow_brackets(printer.rainbow_brackets)
             .term_width(printer.term_width)
             .tab_width(printer.tab_width)
             .loop_through(printer.loop_through)
@@ -141,6 +347,37 @@ impl From<&PrettyPrint> for PrettyPrinter {
             .syntax_mapping(printer.syntax_mapping.clone())
             .pager(printer.pager.clone())
             .use_italic_text(printer.use_italic_text)
+            .language_guessing(printer.language_guessing)
+            .reformat(printer.reformat)
+            .reformat_indent(printer.reformat_indent)
+            .sort_keys(printer.sort_keys)
+            .diff_mode(printer.diff_mode)
+            .diff_context(printer.diff_context)
+            .word_diff(printer.word_diff)
+            .highlight_patches(printer.highlight_patches)
+            .highlight_logs(printer.highlight_logs)
+            .highlight_log_json(printer.highlight_log_json)
+            .embeddings(printer.embeddings.clone())
+            .encoding(printer.encoding.clone())
+            .detect_encoding(printer.detect_encoding)
+            .line_endings(printer.line_endings)
+            .eof_marker(printer.eof_marker)
+            .binary_mode(printer.binary_mode)
+            .hex_bytes_per_row(printer.hex_bytes_per_row)
+            .highlight_pattern(printer.highlight_pattern.clone())
+            .highlight_literals(printer.highlight_literals.clone())
+            .match_context(printer.match_context)
+            .selectors(printer.selectors.clone())
+            .highlight_checkpoints(printer.highlight_checkpoints)
+            .checkpoint_interval(printer.checkpoint_interval)
+            .persist_checkpoints(printer.persist_checkpoints)
+            .max_line_length(printer.max_line_length)
+            .long_lines(printer.long_lines)
+            .highlight_timeout(printer.highlight_timeout)
+            .max_input_bytes(printer.max_input_bytes)
+            .max_input_lines(printer.max_input_lines)
+            .threads(printer.threads)
+            .follow_interval(printer.follow_interval)
             .clone() // As expected, a lot of clone() 😂
     }
 }
@@ -160,17 +397,70 @@ impl PrettyPrint {
             InputFile::Ordinary(file_string)
         };
 
-        self.run_controller(input, None)
+        self.run_controller(input, None, &self.language)
     }
 
     /// Prints a string.
     pub fn string<T: Into<String>>(&self, input: T) -> Result<()> {
-        self.run_controller(InputFile::String(input.into()), None)
+        self.run_controller(InputFile::String(input.into()), None, &self.language)
     }
 
     /// Prints a string with a specific header.
     pub fn string_with_header<T: Into<String>>(&self, input: T, header: T) -> Result<()> {
-        self.run_controller(InputFile::String(input.into()), Some(header.into()))
+        self.run_controller(
+            InputFile::String(input.into()),
+            Some(header.into()),
+            &self.language,
+        )
+    }
+
+    /// Prints the differences between two strings.
+    pub fn diff<T: Into<String>>(&self, old: T, new: T) -> Result<()> {
+        self.run_diff(
+            InputFile::String(old.into()),
+            InputFile::String(new.into()),
+            None,
+        )
+    }
+
+    /// Prints the differences between two files.
+    pub fn diff_files<T: Into<String>>(&self, old: T, new: T) -> Result<()> {
+        let old = old.into();
+        let new = new.into();
+        let header = format!("{} → {}", old, new);
+
+        self.run_diff(
+            InputFile::Ordinary(old),
+            InputFile::Ordinary(new),
+            Some(header),
+        )
+    }
+
+    /// Prints the `Debug` representation (`{:#?}`) of a value as Rust code,
+    /// with the name of its type as header.
+    pub fn debug<T: Debug + ?Sized>(&self, value: &T) -> Result<()> {
+        self.run_controller(
+            InputFile::String(format!("{:#?}", value)),
+            Some(type_name::<T>().to_string()),
+            "rust",
+        )
+    }
+
+    /// Serializes a value into the given format and prints it, with the name
+    /// of its type as header.
+    #[cfg(feature = "serde")]
+    pub fn value<T: Serialize + ?Sized>(&self, value: &T, format: Format) -> Result<()> {
+        self.run_controller(
+            InputFile::String(format.serialize(value)?),
+            Some(type_name::<T>().to_string()),
+            format.language(),
+        )
+    }
+
+    /// Describes which safeguards against long lines and large inputs took
+    /// effect during the last print.
+    pub fn last_report(&self) -> PrintReport {
+        self.report.lock().unwrap().clone()
     }
 
     /// List all available themes for syntax highlighting
@@ -183,44 +473,613 @@ impl PrettyPrint {
         &self,
         input_file: InputFile,
         header_overwrite: Option<String>,
+        language: &str,
     ) -> Result<()> {
         #[cfg(windows)]
         let _ = ansi_term::enable_ansi_support();
         // let interactive_output = atty::is(Stream::Stdout);
 
         let assets = self.get_assets();
+        let prepared = self.prepare_input(&assets, &input_file, language)?;
+
+        let mut output_type = OutputType::from_mode(self.paging_mode, self.pager.clone())?;
+        let writer = output_type.handle()?;
+
+        let report = self.render_input(prepared, writer, &input_file, header_overwrite)?;
+        *self.report.lock().unwrap() = report;
+        Ok(())
+    }
+
+    /// Prints many files. The files are highlighted concurrently, but printed
+    /// in the given order. `last_report` describes the last file. Standard
+    /// input (`-`) can't be one of them.
+    pub fn files<I, T>(&self, filenames: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        #[cfg(windows)]
+        let _ = ansi_term::enable_ansi_support();
+
+        let inputs: Vec<InputFile> = filenames
+            .into_iter()
+            .map(|filename| match filename.into() {
+                ref stdin if stdin == "-" => InputFile::StdIn,
+                filename => InputFile::Ordinary(filename),
+            })
+            .collect();
+        if inputs.contains(&InputFile::StdIn) {
+            return Err("Standard input ('-') can't be printed with `files`".into());
+        }
+
+        // The highlighter state can't be shared between threads, so every
+        // worker gets its own copy of the configuration. The assets are only
+        // loaded once.
+        let config = self.configure();
+        let assets = self.get_assets();
+        let threads = self.threads.unwrap_or_else(default_threads);
+
+        let mut output_type = OutputType::from_mode(self.paging_mode, self.pager.clone())?;
+        let writer = output_type.handle()?;
+
+        render_ordered(
+            &inputs,
+            threads,
+            || Ok(config.build()?),
+            |printer: &mut PrettyPrint, input_file| {
+                let prepared = printer.prepare_input(&assets, input_file, &printer.language)?;
+                let mut buffer = Vec::new();
+                let report = printer.render_input(prepared, &mut buffer, input_file, None)?;
+                Ok((buffer, report))
+            },
+            |(buffer, report)| {
+                writer.write_all(&buffer)?;
+                *self.report.lock().unwrap() = report;
+                Ok(())
+            },
+        )
+    }
+
+    /// Prints a file and then keeps printing lines appended to it, like
+    /// `tail -f`, until `handle` is cancelled or the output budget is
+    /// exhausted. If the file is truncated or replaced (e.g. by log rotation),
+    /// it is printed from its beginning again. Line ranges and selectors
+    /// don't apply, since the file has no fixed end.
+    pub fn follow<T: Into<String>>(&self, filename: T, handle: &FollowHandle) -> Result<()> {
+        #[cfg(windows)]
+        let _ = ansi_term::enable_ansi_support();
+
+        let filename = filename.into();
+        if filename == "-" {
+            return Err("Only files can be followed".into());
+        }
+        let input_file = InputFile::Ordinary(filename.clone());
+
+        let assets = self.get_assets();
+        let mut reader = input_file.get_reader()?;
+        let mut watcher = FileWatcher::new(&filename)?;
+        let mut printer = self.create_printer(
+            &assets,
+            &input_file,
+            &mut reader,
+            &self.language,
+            self.get_output_components(),
+            self.term_width,
+            self.output_wrap,
+        )?;
+        let initial_state = printer.highlight_state();
+
+        let mut output_type = OutputType::from_mode(self.paging_mode, self.pager.clone())?;
+        let writer = output_type.handle()?;
+        printer.print_header(writer, &input_file, None)?;
+
+        let mut line_buffer = Vec::new();
+        let mut line_number = 1;
+        let mut budget = Budget::new(self.max_input_bytes, self.max_input_lines);
+        let mut truncated_after = None;
+        while !handle.is_cancelled() && truncated_after.is_none() {
+            // Lines which are still being written are completed first.
+            if reader.read_line(&mut line_buffer)? && is_complete_line(&line_buffer) {
+                if !print_followed_line(
+                    &mut printer,
+                    writer,
+                    &mut budget,
+                    line_number,
+                    &line_buffer,
+                )? {
+                    truncated_after = Some(line_number - 1);
+                }
+                line_number += 1;
+                line_buffer.clear();
+                continue;
+            }
+
+            // Decoded lines may be longer or shorter than what they were read
+            // from, so the file is compared with the raw input.
+            let label = match watcher.check(reader.position()) {
+                FileChange::Unchanged => {
+                    writer.flush()?;
+                    thread::sleep(self.follow_interval);
+                    continue;
+                }
+                FileChange::Truncated => "file truncated",
+                FileChange::Replaced => {
+                    // Lines appended to the old file before it was replaced
+                    // are still printed.
+                    while truncated_after.is_none()
+                        && reader.read_line(&mut line_buffer)?
+                        && is_complete_line(&line_buffer)
+                    {
+                        if !print_followed_line(
+                            &mut printer,
+                            writer,
+                            &mut budget,
+                            line_number,
+                            &line_buffer,
+                        )? {
+                            truncated_after = Some(line_number - 1);
+                        }
+                        line_number += 1;
+                        line_buffer.clear();
+                    }
+                    "file replaced"
+                }
+            };
+            if truncated_after.is_some() {
+                break;
+            }
+
+            if !line_buffer.is_empty() {
+                if !print_followed_line(
+                    &mut printer,
+                    writer,
+                    &mut budget,
+                    line_number,
+                    &line_buffer,
+                )? {
+                    truncated_after = Some(line_number - 1);
+                    break;
+                }
+                line_buffer.clear();
+            }
+            printer.print_separator(writer, label)?;
+
+            reader = InputFileReader::new(BufReader::new(File::open(&filename)?));
+            if let Some(state) = initial_state.clone() {
+                printer.set_highlight_state(state);
+            }
+            line_number = 1;
+        }
+
+        if !line_buffer.is_empty() && truncated_after.is_none() {
+            print_followed_line(&mut printer, writer, &mut budget, line_number, &line_buffer)?;
+        }
+        printer.print_footer(writer)?;
+        let mut report = printer.take_report();
+        report.truncated_after = truncated_after;
+        *self.report.lock().unwrap() = report;
+        Ok(())
+    }
+
+    /// Detects the syntax of an input and decides which lines to print.
+    fn prepare_input<'a>(
+        &self,
+        assets: &'a HighlightingAssets,
+        input_file: &'a InputFile,
+        language: &str,
+    ) -> Result<PreparedInput<'a>> {
         let mut reader = input_file.get_reader()?;
 
-        let lang_opt = match self.language.as_ref() {
+        let printer = self.create_printer(
+            assets,
+            input_file,
+            &mut reader,
+            language,
+            self.get_output_components(),
+            self.term_width,
+            self.output_wrap,
+        )?;
+
+        // Line numbers refer to the reformatted input from here on.
+        if let Some(syntax) = printer.syntax().filter(|_| self.reformat) {
+            if let Some(reformatted) = self.reformat_input(&mut reader, &syntax.name)? {
+                reader = InputFileReader::new(Cursor::new(reformatted.into_bytes()));
+            }
+        }
+
+        // Ranges of hex dumps refer to rows, and the other ways to select
+        // lines don't apply to them.
+        if let Some(bytes_per_row) = printer.bytes_per_row() {
+            let line_ranges = if self.line_ranges.needs_line_count() {
+                let mut contents = Vec::new();
+                reader.into_raw().read_to_end(&mut contents)?;
+                let rows = contents.len().div_ceil(bytes_per_row);
+                reader = InputFileReader::new(Cursor::new(contents));
+                self.line_ranges.resolve(rows)
+            } else {
+                self.line_ranges.clone()
+            };
+
+            return Ok(PreparedInput {
+                printer,
+                reader,
+                line_ranges,
+                checkpoint_key: None,
+            });
+        }
+
+        // Selectors, ranges relative to the end and checkpoints need the whole
+        // input.
+        let mut checkpoint_key = None;
+        let line_ranges = if self.line_ranges.needs_line_count()
+            || !self.selectors.is_empty()
+            || self.highlight_checkpoints
+        {
+            let mut contents = Vec::new();
+            let mut lines = Vec::new();
+            let mut line_buffer = Vec::new();
+            while reader.read_line(&mut line_buffer)? {
+                lines.push(String::from_utf8_lossy(&line_buffer).into_owned());
+                contents.append(&mut line_buffer);
+            }
+            if self.highlight_checkpoints {
+                checkpoint_key = printer
+                    .syntax()
+                    .map(|syntax| cache_key(&contents, &syntax.name, &self.theme));
+            }
+            reader = InputFileReader::new(Cursor::new(contents));
+
+            let selected = self
+                .selectors
+                .iter()
+                .map(|selector| selector.select(&lines, printer.syntax(), &assets.syntax_set))
+                .collect::<Result<Vec<_>>>()?;
+            self.line_ranges.with(selected).resolve(lines.len())
+        } else {
+            self.line_ranges.clone()
+        };
+
+        Ok(PreparedInput {
+            printer,
+            reader,
+            line_ranges,
+            checkpoint_key,
+        })
+    }
+
+    /// Prints a prepared input and describes which safeguards took effect.
+    fn render_input(
+        &self,
+        prepared: PreparedInput,
+        writer: &mut dyn Write,
+        input_file: &InputFile,
+        header_overwrite: Option<String>,
+    ) -> Result<PrintReport> {
+        let PreparedInput {
+            mut printer,
+            reader,
+            line_ranges,
+            checkpoint_key,
+        } = prepared;
+
+        let truncated_after = self.print_file(
+            reader,
+            &mut printer,
+            writer,
+            input_file,
+            header_overwrite,
+            &line_ranges,
+            checkpoint_key,
+        )?;
+
+        let mut report = printer.take_report();
+        report.truncated_after = truncated_after;
+        Ok(report)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_printer<'a>(
+        &self,
+        assets: &'a HighlightingAssets,
+        input_file: &InputFile,
+        reader: &mut InputFileReader,
+        language: &str,
+        output_components: OutputComponents,
+        term_width: usize,
+        output_wrap: OutputWrap,
+    ) -> Result<InteractivePrinter<'a>> {
+        let highlight_pattern =
+            build_pattern(self.highlight_pattern.as_ref(), &self.highlight_literals)?;
+        let encoding = self
+            .encoding
+            .as_deref()
+            .map(charset::from_label)
+            .transpose()?;
+
+        let lang_opt = match language {
             "unknown" => None,
             s => Some(s.to_string()),
         };
 
-        // This is faaaar from ideal, I know.
-        let mut printer = InteractivePrinter::new(
+        Ok(InteractivePrinter::new(
+            assets,
+            input_file,
+            reader,
+            PrinterOptions {
+                output_components,
+                theme: self.theme.clone(),
+                colored_output: self.colored_output,
+                true_color: self.true_color,
+                term_width,
+                language: lang_opt,
+                syntax_mapping: self.syntax_mapping.clone(),
+                tab_width: self.tab_width,
+                show_nonprintable: self.show_nonprintable,
+                flag_nonprintable: self.flag_nonprintable,
+                audit: self.audit,
+                whitespace_lint: self.whitespace_lint,
+                indent_guides: self.indent_guides,
+                rainbow_brackets: self.rainbow_brackets,
+                output_wrap,
+                use_italic_text: self.use_italic_text,
+                language_guessing: self.language_guessing,
+                highlight_patches: self.highlight_patches,
+                highlight_logs: self.highlight_logs,
+                highlight_log_json: self.highlight_log_json,
+                embeddings: &self.embeddings,
+                encoding,
+                detect_encoding: self.detect_encoding,
+                line_ending_mode: self.line_endings,
+                eof_marker: self.eof_marker,
+                binary_mode: self.binary_mode,
+                bytes_per_row: self.hex_bytes_per_row,
+                highlight_pattern,
+                safeguards: LineSafeguards {
+                    max_line_length: self.max_line_length,
+                    long_lines: self.long_lines,
+                    highlight_timeout: self.highlight_timeout,
+                },
+            },
+        ))
+    }
+
+    fn run_diff(
+        &self,
+        old_file: InputFile,
+        new_file: InputFile,
+        header_overwrite: Option<String>,
+    ) -> Result<()> {
+        #[cfg(windows)]
+        let _ = ansi_term::enable_ansi_support();
+
+        let old = old_file.get_contents()?;
+        let new = new_file.get_contents()?;
+
+        let assets = self.get_assets();
+        let mut old_reader = old_file.get_reader()?;
+        let mut new_reader = new_file.get_reader()?;
+
+        // The frame printer prints everything that spans the whole width,
+        // the old and new printers print the lines of their input.
+        let mut frame = self.create_printer(
             &assets,
-            &input_file,
-            &mut reader,
+            &new_file,
+            &mut new_reader,
+            &self.language,
             self.get_output_components(),
-            self.theme.clone(),
-            self.colored_output,
-            self.true_color,
             self.term_width,
-            lang_opt,
-            self.syntax_mapping.clone(),
-            self.tab_width,
-            self.show_nonprintable,
             self.output_wrap,
-            self.use_italic_text,
-        );
+        )?;
+
+        let mut components = self.get_output_components();
+        components.0.insert(OutputComponent::Changes);
+        components.0.remove(&OutputComponent::Header);
+
+        // In side by side mode, both columns are separated by a single space.
+        let (old_width, new_width, output_wrap) = match self.diff_mode {
+            DiffMode::Unified => (self.term_width, self.term_width, self.output_wrap),
+            DiffMode::SideBySide => {
+                let old_width = self.term_width.saturating_sub(1) / 2;
+                let new_width = self.term_width.saturating_sub(old_width + 1);
+                (old_width, new_width, OutputWrap::Character)
+            }
+        };
+        let mut old_printer = self.create_printer(
+            &assets,
+            &old_file,
+            &mut old_reader,
+            &self.language,
+            components.clone(),
+            old_width,
+            output_wrap,
+        )?;
+        let mut new_printer = self.create_printer(
+            &assets,
+            &new_file,
+            &mut new_reader,
+            &self.language,
+            components,
+            new_width,
+            output_wrap,
+        )?;
 
         let mut output_type = OutputType::from_mode(self.paging_mode, self.pager.clone())?;
         let writer = output_type.handle()?;
 
-        self.print_file(reader, &mut printer, writer, &input_file, header_overwrite)?;
+        frame.print_header(writer, &new_file, header_overwrite)?;
+
+        let blocks = compute_diff(&old, &new, self.word_diff);
+        let last = blocks.len().saturating_sub(1);
+        let mut hidden = 0;
+
+        for (index, block) in blocks.iter().enumerate() {
+            match block {
+                DiffBlock::Equal { old, new } => {
+                    let visible =
+                        visible_lines(new.len(), self.diff_context, index == 0, index == last);
+
+                    for (i, (old_line, new_line)) in old.iter().zip(new).enumerate() {
+                        if !visible(i) {
+                            // Keep the highlighters in sync for skipped lines.
+                            old_printer.print_diff_line(writer, true, old_line, None)?;
+                            new_printer.print_diff_line(writer, true, new_line, None)?;
+                            hidden += 1;
+                            continue;
+                        }
+
+                        self.print_hidden_lines(&mut frame, writer, &mut hidden, "unchanged")?;
+                        self.print_diff_row(
+                            writer,
+                            &mut frame,
+                            (&mut old_printer, Some(old_line), None),
+                            (&mut new_printer, Some(new_line), None),
+                            old_width,
+                        )?;
+                    }
+                }
+                DiffBlock::Change { removed, added } => {
+                    self.print_hidden_lines(&mut frame, writer, &mut hidden, "unchanged")?;
+
+                    if self.diff_mode == DiffMode::Unified {
+                        for line in removed {
+                            old_printer.print_diff_line(
+                                writer,
+                                false,
+                                line,
+                                Some(LineChange::Removed),
+                            )?;
+                        }
+                        for line in added {
+                            new_printer.print_diff_line(
+                                writer,
+                                false,
+                                line,
+                                Some(LineChange::Added),
+                            )?;
+                        }
+                        continue;
+                    }
+
+                    for i in 0..removed.len().max(added.len()) {
+                        self.print_diff_row(
+                            writer,
+                            &mut frame,
+                            (&mut old_printer, removed.get(i), Some(LineChange::Removed)),
+                            (&mut new_printer, added.get(i), Some(LineChange::Added)),
+                            old_width,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        self.print_hidden_lines(&mut frame, writer, &mut hidden, "unchanged")?;
+        frame.print_footer(writer)?;
+
+        Ok(())
+    }
+
+    /// Prints a separator for lines that were collapsed, if there are any.
+    fn print_hidden_lines<P: Printer>(
+        &self,
+        printer: &mut P,
+        writer: &mut dyn Write,
+        hidden: &mut usize,
+        kind: &str,
+    ) -> Result<()> {
+        match *hidden {
+            0 => return Ok(()),
+            1 => printer.print_separator(writer, &format!("1 {} line", kind))?,
+            n => printer.print_separator(writer, &format!("{} {} lines", n, kind))?,
+        }
+        *hidden = 0;
         Ok(())
     }
 
+    /// Prints a line of the old and/or the new input. In unified mode, an
+    /// unchanged line is only printed once.
+    fn print_diff_row(
+        &self,
+        writer: &mut dyn Write,
+        frame: &mut InteractivePrinter,
+        old: (
+            &mut InteractivePrinter,
+            Option<&DiffLine>,
+            Option<LineChange>,
+        ),
+        new: (
+            &mut InteractivePrinter,
+            Option<&DiffLine>,
+            Option<LineChange>,
+        ),
+        column_width: usize,
+    ) -> Result<()> {
+        let (old_printer, old_line, old_change) = old;
+        let (new_printer, new_line, new_change) = new;
+
+        if self.diff_mode == DiffMode::Unified {
+            if let Some(line) = old_line {
+                old_printer.print_diff_line(writer, new_line.is_some(), line, old_change)?;
+            }
+            if let Some(line) = new_line {
+                new_printer.print_diff_line(writer, false, line, new_change)?;
+            }
+            return Ok(());
+        }
+
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        if let Some(line) = old_line {
+            old_printer.print_diff_line(&mut left, false, line, old_change)?;
+        }
+        if let Some(line) = new_line {
+            new_printer.print_diff_line(&mut right, false, line, new_change)?;
+        }
+
+        frame.print_side_by_side(writer, &left, &right, column_width)
+    }
+
+    /// Reformats structured data, which is read from `reader` as it was
+    /// decoded. Returns `None` if the input is in another format, in which
+    /// case nothing is read. Inputs which could not be parsed are returned
+    /// unchanged.
+    #[cfg(feature = "reformat")]
+    fn reformat_input(
+        &self,
+        reader: &mut InputFileReader,
+        syntax_name: &str,
+    ) -> Result<Option<String>> {
+        let format = match DataFormat::from_syntax_name(syntax_name) {
+            Some(format) => format,
+            None => return Ok(None),
+        };
+        let mut input = Vec::new();
+        while reader.read_line(&mut input)? {}
+        let input = String::from_utf8_lossy(&input).into_owned();
+
+        match reformat(&input, format, self.reformat_indent, self.sort_keys) {
+            Ok(output) => Ok(Some(output)),
+            Err(error) => {
+                use ansi_term::Colour::Yellow;
+                eprintln!(
+                    "{}: {}. Printing the input unchanged.",
+                    Yellow.paint("[prettyprint warning]"),
+                    error
+                );
+                Ok(Some(input))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "reformat"))]
+    fn reformat_input(
+        &self,
+        _reader: &mut InputFileReader,
+        _syntax_name: &str,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
+
     fn get_assets(&self) -> HighlightingAssets {
         let syntax_set = self.load_syntax.as_ref().map(|b| from_binary(b.as_slice()));
         let theme_set = self.load_theme.as_ref().map(|b| from_binary(b.as_slice()));
@@ -256,6 +1115,7 @@ impl PrettyPrint {
         OutputComponents(components)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn print_file<'a, P: Printer>(
         &self,
         reader: InputFileReader,
@@ -263,12 +1123,15 @@ impl PrettyPrint {
         writer: &mut dyn Write,
         input_file: &InputFile,
         header_overwrite: Option<String>,
-    ) -> Result<()> {
+        line_ranges: &LineRanges,
+        checkpoint_key: Option<u64>,
+    ) -> Result<Option<usize>> {
         printer.print_header(writer, &input_file, header_overwrite)?;
-        self.print_file_ranges(printer, writer, reader, &self.line_ranges)?;
+        let truncated_after =
+            self.print_file_ranges(printer, writer, reader, line_ranges, checkpoint_key)?;
         printer.print_footer(writer)?;
 
-        Ok(())
+        Ok(truncated_after)
     }
 
     fn print_file_ranges<'a, P: Printer>(
@@ -277,19 +1140,101 @@ impl PrettyPrint {
         writer: &mut dyn Write,
         mut reader: InputFileReader,
         line_ranges: &LineRanges,
-    ) -> Result<()> {
+        checkpoint_key: Option<u64>,
+    ) -> Result<Option<usize>> {
+        if let Some(bytes_per_row) = printer.bytes_per_row() {
+            return self.print_rows(printer, writer, reader, line_ranges, bytes_per_row);
+        }
+
+        let pattern = build_pattern(self.highlight_pattern.as_ref(), &self.highlight_literals)?;
+        if let (Some(pattern), Some(context)) = (pattern, self.match_context) {
+            return self.print_matching_lines(
+                printer,
+                writer,
+                reader,
+                line_ranges,
+                &pattern,
+                context,
+            );
+        }
+
         let mut line_buffer = Vec::new();
         let mut line_number: usize = 1;
+        let mut printed_any = false;
+        let mut previous_in_range = false;
+        let mut previous_name = None;
+        let mut budget = Budget::new(self.max_input_bytes, self.max_input_lines);
+        let mut truncated_after = None;
+
+        // Continue highlighting from the last checkpoint before the first
+        // printed line, instead of highlighting all lines before it.
+        let mut recorder = match (
+            checkpoint_key,
+            printer.highlight_state(),
+            printer.resync_state(),
+        ) {
+            (Some(key), Some(initial), Some(resync)) => Some(Recorder::new(
+                &mut self.checkpoints.lock().unwrap(),
+                key,
+                self.checkpoint_interval,
+                self.persist_checkpoints,
+                initial,
+                resync,
+            )),
+            _ => None,
+        };
+        let mut resume_at = 1;
+        if let Some((line, state)) = recorder
+            .as_ref()
+            .and_then(|recorder| recorder.resume_point(line_ranges.first_line()))
+        {
+            printer.set_highlight_state(state);
+            resume_at = line;
+        }
 
         while reader.read_line(&mut line_buffer)? {
+            if line_number < resume_at {
+                line_number += 1;
+                line_buffer.clear();
+                continue;
+            }
+
+            if let Some(recorder) = recorder.as_mut().filter(|r| r.wants_state(line_number)) {
+                if let Some(state) = printer.highlight_state() {
+                    recorder.record(line_number, state);
+                }
+            }
+
             match line_ranges.check(line_number) {
                 RangeCheckResult::OutsideRange => {
                     // Call the printer in case we need to call the syntax highlighter
                     // for this line. However, set `out_of_range` to `true`.
                     printer.print_line(true, writer, line_number, &line_buffer)?;
+                    previous_in_range = false;
+                    previous_name = None;
                 }
                 RangeCheckResult::InRange => {
+                    // Separate non-contiguous ranges and ranges with different
+                    // names, and show the names of ranges.
+                    let name = line_ranges.name_at(line_number);
+                    if !budget.consume(&line_buffer) {
+                        printer.print_separator(writer, "output truncated")?;
+                        truncated_after = Some(line_number - 1);
+                        break;
+                    }
+                    let starts_range = if previous_in_range {
+                        name != previous_name
+                    } else {
+                        printed_any || name.is_some()
+                    };
+                    if starts_range {
+                        printer.print_separator(writer, name.unwrap_or("…"))?;
+                    }
+
                     printer.print_line(false, writer, line_number, &line_buffer)?;
+                    printed_any = true;
+                    previous_in_range = true;
+                    previous_name = name;
                 }
                 RangeCheckResult::AfterLastRange => {
                     break;
@@ -299,8 +1244,151 @@ impl PrettyPrint {
             line_number += 1;
             line_buffer.clear();
         }
-        Ok(())
+
+        if let Some(recorder) = recorder {
+            recorder.finish(&mut self.checkpoints.lock().unwrap());
+        }
+        Ok(truncated_after)
+    }
+
+    /// Prints a hex dump of the input. Line ranges select rows.
+    fn print_rows<P: Printer>(
+        &self,
+        printer: &mut P,
+        writer: &mut dyn Write,
+        reader: InputFileReader,
+        line_ranges: &LineRanges,
+        bytes_per_row: usize,
+    ) -> Result<Option<usize>> {
+        let mut rows = RowReader::new(reader, bytes_per_row);
+        let mut row = Vec::new();
+        let mut row_number: usize = 1;
+        let mut printed_any = false;
+        let mut previous_in_range = false;
+        let mut previous_name = None;
+        let mut budget = Budget::new(self.max_input_bytes, self.max_input_lines);
+
+        while rows.read_row(&mut row)? {
+            match line_ranges.check(row_number) {
+                RangeCheckResult::OutsideRange => {
+                    previous_in_range = false;
+                    previous_name = None;
+                }
+                RangeCheckResult::InRange => {
+                    let name = line_ranges.name_at(row_number);
+                    if !budget.consume(&row) {
+                        printer.print_separator(writer, "output truncated")?;
+                        return Ok(Some(row_number - 1));
+                    }
+                    let starts_range = if previous_in_range {
+                        name != previous_name
+                    } else {
+                        printed_any || name.is_some()
+                    };
+                    if starts_range {
+                        printer.print_separator(writer, name.unwrap_or("…"))?;
+                    }
+
+                    printer.print_row(writer, (row_number - 1) * bytes_per_row, &row)?;
+                    printed_any = true;
+                    previous_in_range = true;
+                    previous_name = name;
+                }
+                RangeCheckResult::AfterLastRange => break,
+            }
+            row_number += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Prints only the lines matching `pattern`, with `context` lines around
+    /// them. Skipped lines are replaced by a separator.
+    fn print_matching_lines<P: Printer>(
+        &self,
+        printer: &mut P,
+        writer: &mut dyn Write,
+        mut reader: InputFileReader,
+        line_ranges: &LineRanges,
+        pattern: &Regex,
+        context: usize,
+    ) -> Result<Option<usize>> {
+        let mut filter = ContextFilter::new(context);
+        let mut budget = Budget::new(self.max_input_bytes, self.max_input_lines);
+        let mut hidden = 0;
+        let mut line_buffer = Vec::new();
+        let mut line_number: usize = 1;
+
+        while reader.read_line(&mut line_buffer)? {
+            let in_range = match line_ranges.check(line_number) {
+                RangeCheckResult::InRange => true,
+                RangeCheckResult::OutsideRange => false,
+                RangeCheckResult::AfterLastRange => break,
+            };
+            let is_match =
+                !find_matches(pattern, &String::from_utf8_lossy(&line_buffer)).is_empty();
+            let lines = filter.push(
+                line_number,
+                std::mem::take(&mut line_buffer),
+                is_match,
+                in_range,
+            );
+            line_number += 1;
+
+            for (action, number, line) in lines {
+                if action == FilterAction::Show {
+                    if !budget.consume(&line) {
+                        printer.print_separator(writer, "output truncated")?;
+                        return Ok(Some(number - 1));
+                    }
+                    self.print_hidden_lines(printer, writer, &mut hidden, "hidden")?;
+                } else {
+                    hidden += 1;
+                }
+                printer.print_line(action == FilterAction::Hide, writer, number, &line)?;
+            }
+        }
+
+        for (_, number, line) in filter.finish() {
+            hidden += 1;
+            printer.print_line(true, writer, number, &line)?;
+        }
+        self.print_hidden_lines(printer, writer, &mut hidden, "hidden")?;
+        Ok(None)
+    }
+}
+
+/// An input together with the printer for it and the lines to print.
+struct PreparedInput<'a> {
+    printer: InteractivePrinter<'a>,
+    reader: InputFileReader<'a>,
+    line_ranges: LineRanges,
+    checkpoint_key: Option<u64>,
+}
+
+/// Whether a line read from a followed file is complete, or still being
+/// written.
+fn is_complete_line(line: &[u8]) -> bool {
+    !split_line_ending(&String::from_utf8_lossy(line))
+        .1
+        .is_empty()
+}
+
+/// Prints a line of a followed file. Returns `false` if the budget is
+/// exhausted, so that following stops.
+fn print_followed_line<P: Printer>(
+    printer: &mut P,
+    writer: &mut dyn Write,
+    budget: &mut Budget,
+    line_number: usize,
+    line: &[u8],
+) -> Result<bool> {
+    if !budget.consume(line) {
+        printer.print_separator(writer, "output truncated")?;
+        return Ok(false);
     }
+    printer.print_line(false, writer, line_number, line)?;
+    Ok(true)
 }
 
 fn is_truecolor_terminal() -> bool {
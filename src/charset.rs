@@ -0,0 +1,145 @@
+use content_inspector::ContentType;
+use encoding::all::{
+    BIG5_2003, EUC_JP, GBK, UTF_16BE, UTF_16LE, WINDOWS_1252, WINDOWS_31J, WINDOWS_949,
+};
+use encoding::label::encoding_from_whatwg_label;
+use encoding::{EncodingRef, StringWriter};
+
+use crate::errors::*;
+
+/// Multi-byte encodings which are tried if an input isn't valid UTF-8, in
+/// the order of preference if several of them fit.
+const MULTI_BYTE: [EncodingRef; 5] = [WINDOWS_31J, EUC_JP, WINDOWS_949, GBK, BIG5_2003];
+
+/// Looks up an encoding by its WHATWG label, like `latin1` or `shift_jis`.
+pub fn from_label(label: &str) -> Result<EncodingRef> {
+    encoding_from_whatwg_label(label).ok_or_else(|| format!("Unknown encoding '{}'", label).into())
+}
+
+/// The encoding of an input with the given content type, or `None` if it's
+/// UTF-8 (or binary).
+pub fn from_content_type(content_type: ContentType) -> Option<EncodingRef> {
+    match content_type {
+        ContentType::UTF_16LE => Some(UTF_16LE),
+        ContentType::UTF_16BE => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+/// The content type of an input which is known to be in `encoding`.
+pub fn content_type(encoding: EncodingRef) -> ContentType {
+    match encoding.name() {
+        "utf-16le" => ContentType::UTF_16LE,
+        "utf-16be" => ContentType::UTF_16BE,
+        _ => ContentType::UTF_8,
+    }
+}
+
+/// The name of an encoding as shown in the header, e.g. `SHIFT_JIS`.
+pub fn display_name(encoding: EncodingRef) -> String {
+    encoding
+        .whatwg_name()
+        .unwrap_or_else(|| encoding.name())
+        .to_uppercase()
+}
+
+/// Guesses the encoding of the beginning of an input. Returns `None` if it's
+/// valid UTF-8, or if most of its non-ASCII bytes form valid UTF-8 sequences:
+/// then it's UTF-8 with a few broken bytes.
+///
+/// If the non-ASCII bytes mostly come in runs, the multi-byte encoding which
+/// decodes the sample into the fewest characters is chosen, since it pairs
+/// up the most bytes. Otherwise, the input is assumed to be Windows-1252,
+/// which is a superset of Latin-1.
+pub fn detect(sample: &[u8]) -> Option<EncodingRef> {
+    match std::str::from_utf8(sample) {
+        Ok(_) => return None,
+        // The sample may end in the middle of a character.
+        Err(error) if error.error_len().is_none() => return None,
+        Err(_) => {}
+    }
+
+    let (mut valid, mut invalid) = (0, 0);
+    for chunk in sample.utf8_chunks() {
+        valid += chunk.valid().len() - chunk.valid().chars().filter(char::is_ascii).count();
+        invalid += chunk.invalid().len();
+    }
+    if valid > invalid {
+        return None;
+    }
+
+    let non_ascii = sample.iter().filter(|byte| !byte.is_ascii()).count();
+    let in_runs = sample
+        .windows(2)
+        .filter(|pair| !pair[0].is_ascii() && !pair[1].is_ascii())
+        .count();
+    if in_runs < non_ascii / 2 {
+        return Some(WINDOWS_1252);
+    }
+
+    let candidates = MULTI_BYTE
+        .iter()
+        .filter_map(|&encoding| decoded_length(encoding, sample).map(|length| (length, encoding)));
+    let best = candidates.fold(
+        None,
+        |best: Option<(usize, EncodingRef)>, candidate| match best {
+            Some(best) if best.0 <= candidate.0 => Some(best),
+            _ => Some(candidate),
+        },
+    );
+    Some(best.map_or(WINDOWS_1252, |(_, encoding)| encoding))
+}
+
+/// The number of characters `sample` decodes to, or `None` if it isn't valid
+/// in `encoding`. An incomplete character at the end is ignored.
+fn decoded_length(encoding: EncodingRef, sample: &[u8]) -> Option<usize> {
+    let mut decoded = String::new();
+    let mut decoder = encoding.raw_decoder();
+    match decoder.raw_feed(sample, &mut decoded as &mut dyn StringWriter) {
+        (_, None) => Some(decoded.chars().count()),
+        (_, Some(_)) => None,
+    }
+}
+
+#[cfg(test)]
+fn detected_name(sample: &[u8]) -> Option<String> {
+    detect(sample).map(display_name)
+}
+
+#[test]
+fn test_from_label() {
+    assert_eq!("WINDOWS-1252", display_name(from_label("latin1").unwrap()));
+    assert_eq!(
+        "SHIFT_JIS",
+        display_name(from_label(" Shift_JIS ").unwrap())
+    );
+    assert!(from_label("klingon").is_err());
+}
+
+#[test]
+fn test_detect() {
+    use encoding::{EncoderTrap, Encoding};
+
+    assert_eq!(None, detected_name("naïve café".as_bytes()));
+    // A sample cut in the middle of `é`
+    assert_eq!(None, detected_name(&"café".as_bytes()[..4]));
+    assert_eq!(
+        Some("WINDOWS-1252".to_string()),
+        detected_name(b"na\xEFve caf\xE9\n")
+    );
+
+    // UTF-8 with a broken byte
+    assert_eq!(
+        None,
+        detected_name(b"Gr\xC3\xBC\xC3\x9Fe aus M\xC3\xBCnchen \xFF\n")
+    );
+
+    let japanese = WINDOWS_31J
+        .encode("日本語のテキスト\n", EncoderTrap::Strict)
+        .unwrap();
+    assert_eq!(Some("SHIFT_JIS".to_string()), detected_name(&japanese));
+    let japanese = EUC_JP
+        .encode("日本語のテキスト\n", EncoderTrap::Strict)
+        .unwrap();
+    assert_eq!(Some("EUC-JP".to_string()), detected_name(&japanese));
+}
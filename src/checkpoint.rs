@@ -0,0 +1,428 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use syntect::highlighting::HighlightState;
+use syntect::parsing::ParseState;
+
+use crate::dirs::PROJECT_DIRS;
+
+/// The state of the highlighter before a line.
+pub type HighlightCheckpoint = (HighlightState, ParseState);
+
+/// Identifies an input together with the syntax and theme used to highlight
+/// it, since checkpoints are only valid for this combination.
+///
+/// Keys name the files of the stored restart points, so they are computed
+/// with FNV-1a, which is the same across builds, unlike the standard hasher.
+pub fn cache_key(contents: &[u8], syntax: &str, theme: &str) -> u64 {
+    let mut hash = Fnv1a::default();
+    for part in [contents, syntax.as_bytes(), theme.as_bytes()] {
+        hash.write(&(part.len() as u64).to_le_bytes());
+        hash.write(part);
+    }
+    hash.0
+}
+
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Highlighter states of one input, by the line number they precede.
+#[derive(Debug, Default)]
+pub struct Checkpoints {
+    states: BTreeMap<usize, HighlightCheckpoint>,
+}
+
+impl Checkpoints {
+    pub fn insert(&mut self, line_number: usize, state: HighlightCheckpoint) {
+        self.states.insert(line_number, state);
+    }
+
+    /// The last checkpoint at or before the given line.
+    pub fn latest_before(&self, line_number: usize) -> Option<(usize, &HighlightCheckpoint)> {
+        self.states
+            .range(..=line_number)
+            .next_back()
+            .map(|(line, state)| (*line, state))
+    }
+}
+
+/// Checkpoints of all inputs printed by one `PrettyPrint`.
+#[derive(Debug, Default)]
+pub struct CheckpointCache {
+    inputs: HashMap<u64, Checkpoints>,
+}
+
+impl CheckpointCache {
+    /// Removes the checkpoints of an input from the cache, so that they can
+    /// be updated without holding on to the cache.
+    pub fn take(&mut self, key: u64) -> Checkpoints {
+        self.inputs.remove(&key).unwrap_or_default()
+    }
+
+    pub fn insert(&mut self, key: u64, checkpoints: Checkpoints) {
+        self.inputs.insert(key, checkpoints);
+    }
+}
+
+/// Records checkpoints while an input is printed, and finds the checkpoint
+/// to continue from when only later lines are printed.
+pub struct Recorder {
+    key: u64,
+    interval: usize,
+    persist: bool,
+    checkpoints: Checkpoints,
+    initial: HighlightCheckpoint,
+    resync: HighlightCheckpoint,
+    resync_points: Vec<usize>,
+    /// The line up to which a restart point is looked for
+    resync_search_end: usize,
+}
+
+/// The number of lines after a checkpoint in which a restart point is looked
+/// for, since the state of the highlighter is copied for each of them.
+const RESYNC_SEARCH_LINES: usize = 100;
+
+impl Recorder {
+    /// `initial` is the state of a fresh highlighter, and `resync` its state
+    /// after an empty line: a fresh highlighter can restart at lines which
+    /// begin in that state. With `persist`, restart points are loaded from
+    /// and stored to disk.
+    pub fn new(
+        cache: &mut CheckpointCache,
+        key: u64,
+        interval: usize,
+        persist: bool,
+        initial: HighlightCheckpoint,
+        resync: HighlightCheckpoint,
+    ) -> Self {
+        Recorder {
+            key,
+            interval: interval.max(1),
+            persist,
+            checkpoints: cache.take(key),
+            initial,
+            resync,
+            resync_points: if persist {
+                load_resync_points(key)
+            } else {
+                vec![]
+            },
+            resync_search_end: 0,
+        }
+    }
+
+    /// The latest line at or before `target` at which highlighting can
+    /// continue, with the state to continue from.
+    pub fn resume_point(&self, target: usize) -> Option<(usize, HighlightCheckpoint)> {
+        let memory = self.checkpoints.latest_before(target);
+        let disk = self.resync_points.iter().rev().find(|&&p| p <= target);
+
+        match (memory, disk) {
+            (Some((line, _)), Some(&point)) if point > line => Some((point, self.initial.clone())),
+            (Some((line, state)), _) => Some((line, state.clone())),
+            (None, Some(&point)) => Some((point, self.initial.clone())),
+            (None, None) => None,
+        }
+        .filter(|(line, _)| *line > 1)
+    }
+
+    /// Whether `record` should be called for this line.
+    pub fn wants_state(&self, line_number: usize) -> bool {
+        line_number > 1
+            && (self.is_checkpoint_line(line_number) || line_number <= self.resync_search_end)
+    }
+
+    fn is_checkpoint_line(&self, line_number: usize) -> bool {
+        (line_number - 1).is_multiple_of(self.interval)
+    }
+
+    /// Records the state of the highlighter before the given line.
+    pub fn record(&mut self, line_number: usize, state: HighlightCheckpoint) {
+        if self.persist && self.is_checkpoint_line(line_number) {
+            self.resync_search_end = line_number + RESYNC_SEARCH_LINES;
+        }
+
+        if line_number <= self.resync_search_end && state == self.resync {
+            if !self.resync_points.contains(&line_number) {
+                self.resync_points.push(line_number);
+            }
+            self.resync_search_end = 0;
+        }
+
+        if self.is_checkpoint_line(line_number) {
+            self.checkpoints.insert(line_number, state);
+        }
+    }
+
+    /// Puts the checkpoints back into the cache, and stores the restart
+    /// points if they should be persisted.
+    pub fn finish(mut self, cache: &mut CheckpointCache) {
+        if self.persist {
+            self.resync_points.sort_unstable();
+            store_resync_points(self.key, &self.resync_points);
+        }
+        cache.insert(self.key, self.checkpoints);
+    }
+}
+
+/// Parser states can't be stored on disk. Instead, the lines at which the
+/// highlighter is back in its initial state are stored, since highlighting
+/// can restart there with a fresh highlighter (e.g. between two functions).
+fn resync_points_dir() -> PathBuf {
+    PROJECT_DIRS.cache_dir().join("checkpoints")
+}
+
+fn resync_points_path(key: u64) -> PathBuf {
+    resync_points_dir().join(format!("{:016x}", key))
+}
+
+/// The number of inputs for which restart points are kept on disk
+const MAX_STORED_INPUTS: usize = 1000;
+
+/// The time after which the restart points of an input which was not printed
+/// again are removed
+const MAX_STORED_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Loads the stored restart points of an input, in ascending order.
+pub fn load_resync_points(key: u64) -> Vec<usize> {
+    let contents = fs::read_to_string(resync_points_path(key)).unwrap_or_default();
+    let mut points: Vec<usize> = contents.lines().filter_map(|l| l.parse().ok()).collect();
+    points.sort_unstable();
+    points
+}
+
+/// Stores the restart points of an input. Failures are ignored, as the cache
+/// is only an optimization.
+///
+/// The points are rewritten whenever an input is printed, so the least
+/// recently printed inputs are evicted when one is added.
+pub fn store_resync_points(key: u64, points: &[usize]) {
+    let path = resync_points_path(key);
+    let contents: String = points.iter().map(|p| format!("{}\n", p)).collect();
+
+    if !path.exists() {
+        evict_resync_points(&resync_points_dir(), MAX_STORED_INPUTS - 1, MAX_STORED_AGE);
+    }
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, contents);
+}
+
+/// Removes the files in `dir` which were modified longer than `max_age` ago,
+/// and the oldest ones beyond `max_files`.
+fn evict_resync_points(dir: &Path, max_files: usize, max_age: Duration) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    files.sort_unstable_by_key(|(modified, _)| Reverse(*modified));
+
+    let now = SystemTime::now();
+    for (index, (modified, path)) in files.iter().enumerate() {
+        let expired = now.duration_since(*modified).is_ok_and(|age| age > max_age);
+        if index >= max_files || expired {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+fn state(scope: &str) -> HighlightCheckpoint {
+    use std::str::FromStr;
+    use syntect::highlighting::{Highlighter, Theme};
+    use syntect::parsing::syntax_definition::{Context, SyntaxDefinition};
+    use syntect::parsing::{Scope, ScopeStack, SyntaxSetBuilder};
+
+    let mut builder = SyntaxSetBuilder::new();
+    builder.add(SyntaxDefinition {
+        name: "Plain Text".to_string(),
+        file_extensions: vec!["txt".to_string()],
+        scope: Scope::new("text.plain").unwrap(),
+        first_line_match: None,
+        hidden: false,
+        variables: Default::default(),
+        contexts: ["__start", "main"]
+            .iter()
+            .map(|name| (name.to_string(), Context::new(false)))
+            .collect(),
+    });
+    let syntax_set = builder.build();
+
+    let theme = Theme::default();
+    let highlighter = Highlighter::new(&theme);
+    (
+        HighlightState::new(&highlighter, ScopeStack::from_str(scope).unwrap()),
+        ParseState::new(syntax_set.find_syntax_plain_text()),
+    )
+}
+
+#[test]
+fn test_latest_checkpoint() {
+    let mut checkpoints = Checkpoints::default();
+    assert!(checkpoints.latest_before(5000).is_none());
+
+    checkpoints.insert(1001, state("source"));
+    checkpoints.insert(2001, state("source"));
+    assert!(checkpoints.latest_before(1000).is_none());
+    assert_eq!(Some(1001), checkpoints.latest_before(1001).map(|c| c.0));
+    assert_eq!(Some(1001), checkpoints.latest_before(2000).map(|c| c.0));
+    assert_eq!(Some(2001), checkpoints.latest_before(90000).map(|c| c.0));
+}
+
+#[test]
+fn test_cache_key() {
+    let key = cache_key(b"fn main() {}", "Rust", "Monokai");
+    assert_eq!(key, cache_key(b"fn main() {}", "Rust", "Monokai"));
+    assert_ne!(key, cache_key(b"fn main() {}", "C", "Monokai"));
+    assert_ne!(key, cache_key(b"fn main() {}", "Rust", "GitHub"));
+    assert_ne!(key, cache_key(b"fn main() { }", "Rust", "Monokai"));
+}
+
+#[test]
+fn test_cache_key_is_stable() {
+    assert_eq!(0xcbf2_9ce4_8422_2325, Fnv1a::default().0);
+    let mut hash = Fnv1a::default();
+    hash.write(b"a");
+    assert_eq!(0xaf63_dc4c_8601_ec8c, hash.0);
+
+    assert_ne!(cache_key(b"a", "bc", ""), cache_key(b"ab", "c", ""));
+}
+
+#[test]
+fn test_evict_resync_points() {
+    let dir = std::env::temp_dir().join(format!("prettyprint-{}-checkpoints", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let now = SystemTime::now();
+    for (name, age) in [("a", 0), ("b", 10), ("c", 20), ("d", 100)] {
+        let file = fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(now - Duration::from_secs(age)).unwrap();
+    }
+
+    evict_resync_points(&dir, 2, Duration::from_secs(50));
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(vec!["a", "b"], names);
+}
+
+#[test]
+fn test_recorder() {
+    let mut cache = CheckpointCache::default();
+    let mut recorder = Recorder::new(&mut cache, 1, 10, false, state("source"), state("source"));
+
+    assert!(!recorder.wants_state(1));
+    assert!(!recorder.wants_state(10));
+    assert!(recorder.wants_state(11));
+    recorder.record(11, state("source string"));
+    recorder.record(21, state("source string"));
+
+    assert!(recorder.resume_point(10).is_none());
+    let (line, resumed) = recorder.resume_point(25).unwrap();
+    assert_eq!(21, line);
+    assert_eq!(state("source string"), resumed);
+    recorder.finish(&mut cache);
+
+    // Checkpoints are kept for later prints of the same input.
+    let recorder = Recorder::new(&mut cache, 1, 10, false, state("source"), state("source"));
+    assert_eq!(Some(11), recorder.resume_point(15).map(|p| p.0));
+}
+
+#[test]
+fn test_recorder_resync_points() {
+    let mut cache = CheckpointCache::default();
+    let mut recorder = Recorder::new(&mut cache, 2, 10, false, state("source"), state("source"));
+    recorder.persist = true;
+
+    recorder.record(11, state("source string"));
+    assert!(recorder.wants_state(12));
+    recorder.record(12, state("source string"));
+    recorder.record(13, state("source"));
+    assert!(!recorder.wants_state(14));
+    assert_eq!(vec![13], recorder.resync_points);
+
+    // Restart points after the latest checkpoint start from a fresh state.
+    let (line, resumed) = recorder.resume_point(20).unwrap();
+    assert_eq!(13, line);
+    assert_eq!(state("source"), resumed);
+}
+
+#[test]
+fn test_recorder_finds_resync_points_while_highlighting() {
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::Theme;
+    use syntect::parsing::{SyntaxDefinition, SyntaxSetBuilder};
+
+    let definition = r#"
+name: Blocks
+scope: source.blocks
+file_extensions: [blocks]
+contexts:
+  main:
+    - match: '\{'
+      push: block
+  block:
+    - meta_scope: meta.block
+    - match: '\}'
+      pop: true
+"#;
+    let mut builder = SyntaxSetBuilder::new();
+    builder.add(SyntaxDefinition::load_from_str(definition, true, None).unwrap());
+    let syntax_set = builder.build();
+    let syntax = syntax_set.find_syntax_by_name("Blocks").unwrap();
+    let theme = Theme::default();
+
+    let fresh = HighlightLines::new(syntax, &theme).state();
+    let mut highlighter = HighlightLines::new(syntax, &theme);
+    highlighter.highlight_line("\n", &syntax_set).unwrap();
+    let resync = highlighter.state();
+
+    let mut cache = CheckpointCache::default();
+    let mut recorder = Recorder::new(&mut cache, 3, 2, false, fresh.clone(), resync);
+    recorder.persist = true;
+
+    let mut highlighter = HighlightLines::new(syntax, &theme);
+    let lines = ["a {\n", "  b\n", "}\n", "c {\n", "  d\n", "}\n"];
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        if recorder.wants_state(line_number) {
+            let (highlight_state, parse_state) = highlighter.state();
+            recorder.record(line_number, (highlight_state.clone(), parse_state.clone()));
+            highlighter = HighlightLines::from_state(&theme, highlight_state, parse_state);
+        }
+        highlighter.highlight_line(line, &syntax_set).unwrap();
+    }
+
+    // Line 3 is within the first block, line 4 is the first one after it.
+    assert_eq!(vec![4], recorder.resync_points);
+    let (line, resumed) = recorder.resume_point(4).unwrap();
+    assert_eq!(4, line);
+    assert_eq!(fresh, resumed);
+}
@@ -1,5 +1,6 @@
 use ansi_term::Style;
 
+use crate::diff::LineChange;
 use crate::printer::{Colors, InteractivePrinter};
 
 #[derive(Clone)]
@@ -68,6 +69,92 @@ impl Decoration for LineNumberDecoration {
     }
 }
 
+pub struct LineChangesDecoration {
+    cached_none: DecorationText,
+    cached_added: DecorationText,
+    cached_removed: DecorationText,
+}
+
+impl LineChangesDecoration {
+    #[inline]
+    fn generate_cached(style: Style, text: &str) -> DecorationText {
+        DecorationText {
+            text: style.paint(text).to_string(),
+            width: text.chars().count(),
+        }
+    }
+
+    pub fn new(colors: &Colors) -> Self {
+        LineChangesDecoration {
+            cached_none: Self::generate_cached(Style::default(), " "),
+            cached_added: Self::generate_cached(colors.git_added, "+"),
+            cached_removed: Self::generate_cached(colors.git_removed, "-"),
+        }
+    }
+}
+
+impl Decoration for LineChangesDecoration {
+    fn generate(
+        &self,
+        _line_number: usize,
+        continuation: bool,
+        printer: &InteractivePrinter,
+    ) -> DecorationText {
+        if !continuation {
+            match printer.line_change() {
+                Some(LineChange::Added) => return self.cached_added.clone(),
+                Some(LineChange::Removed) => return self.cached_removed.clone(),
+                None => {}
+            }
+        }
+
+        self.cached_none.clone()
+    }
+
+    fn width(&self) -> usize {
+        self.cached_none.width
+    }
+}
+
+pub struct AuditDecoration {
+    cached_none: DecorationText,
+    cached_flagged: DecorationText,
+}
+
+impl AuditDecoration {
+    pub fn new(colors: &Colors) -> Self {
+        AuditDecoration {
+            cached_none: DecorationText {
+                text: " ".to_string(),
+                width: 1,
+            },
+            cached_flagged: DecorationText {
+                text: colors.audit_marker.paint("!").to_string(),
+                width: 1,
+            },
+        }
+    }
+}
+
+impl Decoration for AuditDecoration {
+    fn generate(
+        &self,
+        _line_number: usize,
+        continuation: bool,
+        printer: &InteractivePrinter,
+    ) -> DecorationText {
+        if !continuation && printer.line_flagged() {
+            self.cached_flagged.clone()
+        } else {
+            self.cached_none.clone()
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.cached_none.width
+    }
+}
+
 pub struct GridBorderDecoration {
     cached: DecorationText,
 }
@@ -0,0 +1,409 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::inputfile::InputFile;
+
+/// Number of lines at the start and at the end of a file that are searched
+/// for modelines (vim uses 5 by default).
+const MODELINE_LINES: usize = 5;
+
+/// Number of bytes at the end of a file that are read to find the last lines.
+const TRAILER_BYTES: u64 = 4096;
+
+lazy_static! {
+    static ref VIM_MODELINE: Regex =
+        Regex::new(r"(?:^|\s)(?:vim?|Vim|ex)(?:[<=>]?\d+)?:\s*(?:se(?:t)?\s+([^:]*)|(.*))")
+            .unwrap();
+    static ref EMACS_MODELINE: Regex = Regex::new(r"-\*-(.*?)-\*-").unwrap();
+    static ref EMACS_LOCAL_MODE: Regex = Regex::new(r"(?i)\bmode:\s*([\w+.-]+)").unwrap();
+}
+
+/// Parses vim (`vim: set ft=python:`) and emacs (`-*- mode: ruby -*-`)
+/// modelines and returns the normalized file type.
+pub fn detect_modeline(lines: &[String]) -> Option<String> {
+    lines
+        .iter()
+        .find_map(|line| vim_modeline(line).or_else(|| emacs_modeline(line)))
+        .or_else(|| emacs_local_variables(lines))
+        .map(|file_type| normalize_file_type(&file_type))
+}
+
+fn vim_modeline(line: &str) -> Option<String> {
+    let captures = VIM_MODELINE.captures(line)?;
+    let options = captures.get(1).or_else(|| captures.get(2))?.as_str();
+
+    options
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .filter_map(|option| {
+            let mut parts = option.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("ft"), Some(value))
+                | (Some("filetype"), Some(value))
+                | (Some("syn"), Some(value))
+                | (Some("syntax"), Some(value)) => Some(value),
+                _ => None,
+            }
+        })
+        .find(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+fn emacs_modeline(line: &str) -> Option<String> {
+    let inner = EMACS_MODELINE.captures(line)?.get(1)?.as_str().trim();
+
+    if !inner.contains(':') {
+        return Some(inner.to_string()).filter(|mode| !mode.is_empty());
+    }
+
+    inner.split(';').find_map(|variable| {
+        let mut parts = variable.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case("mode") => {
+                Some(value.trim().to_string())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Handles the `Local Variables:` ... `End:` block at the end of a file.
+fn emacs_local_variables(lines: &[String]) -> Option<String> {
+    let start = lines
+        .iter()
+        .position(|line| line.contains("Local Variables:"))?;
+
+    lines[start + 1..]
+        .iter()
+        .take_while(|line| !line.contains("End:"))
+        .find_map(|line| EMACS_LOCAL_MODE.captures(line))
+        .map(|captures| captures[1].to_string())
+}
+
+/// Maps vim file types and emacs major modes onto syntax tokens.
+fn normalize_file_type(file_type: &str) -> String {
+    let file_type = file_type.trim().to_lowercase();
+    let file_type = file_type
+        .trim_end_matches("-mode")
+        .trim_end_matches("-ts")
+        .trim_end_matches("-script");
+
+    match file_type {
+        "c++" => "cpp",
+        "shell" | "bash" | "zsh" => "sh",
+        "js" | "js2" | "js3" => "js",
+        "make" | "makefile-gmake" => "Makefile",
+        "conf" | "conf-unix" => "ini",
+        "emacs-lisp" | "elisp" => "lisp",
+        "cperl" => "perl",
+        "latex" => "tex",
+        "text" => "txt",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Determines a syntax token from the shebang in the first line.
+pub fn detect_shebang(lines: &[String]) -> Option<String> {
+    lines
+        .first()
+        .and_then(|line| shebang_interpreter(line))
+        .and_then(|interpreter| interpreter_token(&interpreter))
+}
+
+/// Extracts the interpreter name from a shebang line.
+///
+/// `env` (including `env -S` and environment assignments) is resolved to the
+/// actual interpreter, and version suffixes like in `python3.11` are removed.
+pub fn shebang_interpreter(line: &str) -> Option<String> {
+    let command = line.trim_start_matches('\u{feff}').strip_prefix("#!")?;
+    let mut words = command.split_whitespace();
+    let mut program = basename(words.next()?);
+
+    if program == "env" {
+        let mut interpreter = None;
+        let mut skip_next = false;
+
+        for word in words {
+            if skip_next {
+                skip_next = false;
+            } else if word == "-u" || word == "-C" || word == "--unset" || word == "--chdir" {
+                skip_next = true;
+            } else if let Some(split) = word.strip_prefix("-S").filter(|s| !s.is_empty()) {
+                interpreter = Some(split);
+                break;
+            } else if word.starts_with('-') || word.contains('=') {
+                continue;
+            } else {
+                interpreter = Some(word);
+                break;
+            }
+        }
+
+        program = basename(interpreter?);
+    }
+
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    Some(program.to_string()).filter(|p| !p.is_empty())
+}
+
+fn basename(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+/// Maps an interpreter name onto a syntax token.
+pub fn interpreter_token(interpreter: &str) -> Option<String> {
+    let token = match interpreter {
+        "sh" | "bash" | "dash" | "ash" | "zsh" | "ksh" | "mksh" => "sh",
+        "fish" => "fish",
+        "python" | "pypy" => "py",
+        "ruby" | "jruby" | "macruby" => "rb",
+        "perl" => "pl",
+        "node" | "nodejs" | "bun" | "rhino" => "js",
+        "deno" | "ts-node" | "tsx" => "ts",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "Rscript" | "r" => "r",
+        "tclsh" | "wish" => "tcl",
+        "awk" | "gawk" | "mawk" | "nawk" => "awk",
+        "make" | "gmake" => "Makefile",
+        "pwsh" | "powershell" => "ps1",
+        "groovy" => "groovy",
+        "scala" => "scala",
+        "runhaskell" | "runghc" | "stack" => "hs",
+        "elixir" => "ex",
+        "crystal" => "cr",
+        "julia" => "jl",
+        "swift" => "swift",
+        "kotlin" => "kts",
+        "osascript" => "applescript",
+        "dart" => "dart",
+        "nix-shell" => "nix",
+        _ => return None,
+    };
+    Some(token.to_string())
+}
+
+/// Reads the lines that may contain modelines or shebangs: the first and the
+/// last `MODELINE_LINES` lines of the input. Inputs which can't be read twice,
+/// like standard input or pipes, only have their first line checked, which the
+/// reader already buffered.
+pub fn candidate_lines(file: &InputFile, first_line: &[u8]) -> Vec<String> {
+    let first_line = || {
+        String::from_utf8_lossy(first_line)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+
+    match file {
+        InputFile::Ordinary(filename) => match file_candidate_lines(filename) {
+            Ok(Some(lines)) => lines,
+            Ok(None) => first_line(),
+            Err(_) => vec![],
+        },
+        InputFile::String(s) => {
+            let lines: Vec<&str> = s.lines().collect();
+            header_and_trailer(&lines)
+        }
+        InputFile::StdIn => first_line(),
+    }
+}
+
+/// Reads the candidate lines of a regular file, or returns `None` for other
+/// files.
+fn file_candidate_lines(filename: &str) -> std::io::Result<Option<Vec<String>>> {
+    if !fs::metadata(filename)?.is_file() {
+        return Ok(None);
+    }
+    let mut file = File::open(filename)?;
+    let length = file.metadata()?.len();
+
+    if length <= TRAILER_BYTES {
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        let content = String::from_utf8_lossy(&content);
+        let lines: Vec<&str> = content.lines().collect();
+        return Ok(Some(header_and_trailer(&lines)));
+    }
+
+    // Long lines, e.g. of minified files, are only read up to a limit.
+    let mut lines: Vec<String> = BufReader::new(&mut file)
+        .take(TRAILER_BYTES)
+        .split(b'\n')
+        .take(MODELINE_LINES)
+        .map(|line| line.map(|l| String::from_utf8_lossy(&l).into_owned()))
+        .collect::<std::io::Result<_>>()?;
+
+    file.seek(SeekFrom::End(-(TRAILER_BYTES as i64)))?;
+    let mut trailer = Vec::new();
+    file.read_to_end(&mut trailer)?;
+    let trailer = String::from_utf8_lossy(&trailer);
+
+    // The first line of the trailer is most likely incomplete.
+    let trailer: Vec<&str> = trailer.lines().skip(1).collect();
+    let tail = trailer.len().saturating_sub(MODELINE_LINES);
+    lines.extend(trailer[tail..].iter().map(|l| l.to_string()));
+
+    Ok(Some(lines))
+}
+
+fn header_and_trailer(lines: &[&str]) -> Vec<String> {
+    let head = lines.len().min(MODELINE_LINES);
+    let tail = lines.len().saturating_sub(MODELINE_LINES).max(head);
+
+    lines[..head]
+        .iter()
+        .chain(lines[tail..].iter())
+        .map(|l| l.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+fn detect(text: &str) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let lines = header_and_trailer(&lines);
+    detect_modeline(&lines).or_else(|| detect_shebang(&lines))
+}
+
+#[test]
+fn test_vim_modelines() {
+    let corpus = [
+        ("# vim: ft=python", Some("python")),
+        ("# vim: set ft=python:", Some("python")),
+        ("/* vim: set ts=4 sw=4 filetype=c: */", Some("c")),
+        ("// vi: syntax=javascript", Some("javascript")),
+        ("# vim600: set foldmethod=marker ft=yaml:", Some("yaml")),
+        ("# ex: ft=ruby", Some("ruby")),
+        ("// vim:ft=cpp:ts=2", Some("cpp")),
+        ("# vim: set ts=4:", None),
+        ("# evim: ft=python", None),
+        ("let vim = 1; // no modeline", None),
+    ];
+
+    for (line, expected) in corpus.iter() {
+        assert_eq!(
+            expected.map(String::from),
+            detect(line),
+            "modeline: {:?}",
+            line
+        );
+    }
+}
+
+#[test]
+fn test_emacs_modelines() {
+    let corpus = [
+        ("# -*- mode: ruby -*-", Some("ruby")),
+        (
+            "# -*- Mode: Python; indent-tabs-mode: nil -*-",
+            Some("python"),
+        ),
+        ("// -*-c++-*-", Some("cpp")),
+        (";; -*- emacs-lisp -*-", Some("lisp")),
+        ("# -*- coding: utf-8 -*-", None),
+        (
+            "#!/bin/sh\n# -*- mode: sh-mode; -*-\necho hello",
+            Some("sh"),
+        ),
+    ];
+
+    for (text, expected) in corpus.iter() {
+        assert_eq!(
+            expected.map(String::from),
+            detect(text),
+            "modeline: {:?}",
+            text
+        );
+    }
+}
+
+#[test]
+fn test_trailing_modelines() {
+    let text = "line 1\nline 2\nline 3\nline 4\nline 5\nline 6\nline 7\n# vim: ft=make";
+    assert_eq!(Some("Makefile".to_string()), detect(text));
+
+    let text = "x = 1\n\n# Local Variables:\n# mode: python\n# End:";
+    assert_eq!(Some("python".to_string()), detect(text));
+
+    let mut lines = vec!["filler"; 20];
+    lines[10] = "# vim: ft=python";
+    assert_eq!(None, detect(&lines.join("\n")));
+}
+
+#[test]
+fn test_shebangs() {
+    let corpus = [
+        ("#!/bin/bash", Some("bash")),
+        ("#! /bin/sh -e", Some("sh")),
+        ("#!/usr/bin/env python3", Some("python")),
+        ("#!/usr/bin/python3.11 -u", Some("python")),
+        ("#!/usr/bin/env -S deno run --allow-net", Some("deno")),
+        ("#!/usr/bin/env -Snode --harmony", Some("node")),
+        ("#!/usr/bin/env -i PATH=/bin ruby -w", Some("ruby")),
+        ("#!/usr/bin/env -u HOME perl", Some("perl")),
+        ("\u{feff}#!/usr/local/bin/lua5.1", Some("lua")),
+        ("#!/usr/bin/env", None),
+        ("# not a shebang", None),
+    ];
+
+    for (line, expected) in corpus.iter() {
+        assert_eq!(
+            expected.map(String::from),
+            shebang_interpreter(line),
+            "shebang: {:?}",
+            line
+        );
+    }
+}
+
+#[test]
+fn test_detect_shebangs() {
+    assert_eq!(
+        Some("ts".to_string()),
+        detect("#!/usr/bin/env -S deno run\nconsole.log(1)")
+    );
+    assert_eq!(
+        Some("py".to_string()),
+        detect("#!/usr/bin/env python\nprint(1)")
+    );
+    assert_eq!(
+        Some("ruby".to_string()),
+        detect("#!/bin/sh\n# -*- mode: ruby -*-\nputs 1")
+    );
+    assert_eq!(None, detect("#!/usr/bin/env unknown-interpreter"));
+    assert_eq!(None, detect(""));
+}
+
+#[test]
+fn test_stdin_candidate_lines() {
+    let lines = candidate_lines(&InputFile::StdIn, b"#!/usr/bin/env python\n");
+    assert_eq!(vec!["#!/usr/bin/env python".to_string()], lines);
+    assert_eq!(Some("py".to_string()), detect_shebang(&lines));
+}
+
+#[test]
+fn test_file_candidate_lines() {
+    let path = std::env::temp_dir().join(format!("prettyprint-{}-minified.js", std::process::id()));
+    let mut content = "x".repeat(TRAILER_BYTES as usize * 4);
+    content.push_str("\n// vim: ft=javascript\n");
+    fs::write(&path, &content).unwrap();
+    let lines = candidate_lines(&InputFile::Ordinary(path.to_string_lossy().into()), b"");
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(TRAILER_BYTES as usize, lines[0].len());
+    assert_eq!(Some("javascript".to_string()), detect_modeline(&lines));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_special_file_candidate_lines() {
+    // Files which aren't regular, like pipes, aren't read again.
+    let lines = candidate_lines(&InputFile::Ordinary("/dev/null".into()), b"#!/bin/sh\n");
+    assert_eq!(vec!["#!/bin/sh".to_string()], lines);
+}
@@ -0,0 +1,209 @@
+use std::ops::Range;
+
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+/// How a diff is laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DiffMode {
+    /// Removed and added lines below each other, like `diff -u`
+    #[default]
+    Unified,
+    /// Old and new input in two columns next to each other
+    SideBySide,
+}
+
+/// Marks a printed line as added or removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineChange {
+    Added,
+    Removed,
+}
+
+/// A single line of one of the two inputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    /// Line number within its input, starting at 1
+    pub number: usize,
+    pub text: String,
+    /// Byte ranges of the words that changed within the line
+    pub emphasis: Vec<Range<usize>>,
+}
+
+/// A sequence of lines that are either identical or changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffBlock {
+    Equal {
+        old: Vec<DiffLine>,
+        new: Vec<DiffLine>,
+    },
+    Change {
+        removed: Vec<DiffLine>,
+        added: Vec<DiffLine>,
+    },
+}
+
+/// Computes the line diff between `old` and `new`. With `word_diff`, the
+/// changed words within changed lines are marked as well.
+pub fn compute_diff(old: &str, new: &str, word_diff: bool) -> Vec<DiffBlock> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut blocks: Vec<DiffBlock> = Vec::new();
+
+    for op in diff.ops() {
+        let mut old_lines = Vec::new();
+        let mut new_lines = Vec::new();
+
+        let mut push = |tag: ChangeTag, line: DiffLine| match tag {
+            ChangeTag::Delete => old_lines.push(line),
+            ChangeTag::Insert => new_lines.push(line),
+            ChangeTag::Equal => {
+                old_lines.push(line.clone());
+                new_lines.push(line);
+            }
+        };
+
+        if word_diff {
+            for change in diff.iter_inline_changes(op) {
+                let mut text = String::new();
+                let mut emphasis = Vec::new();
+                for (emphasized, value) in change.iter_strings_lossy() {
+                    if emphasized {
+                        emphasis.push(text.len()..text.len() + value.len());
+                    }
+                    text.push_str(&value);
+                }
+                let number = change.new_index().or(change.old_index()).unwrap_or(0) + 1;
+                push(
+                    change.tag(),
+                    DiffLine {
+                        number,
+                        text,
+                        emphasis,
+                    },
+                );
+            }
+        } else {
+            for change in diff.iter_changes(op) {
+                let number = change.new_index().or(change.old_index()).unwrap_or(0) + 1;
+                let text = change.to_string_lossy().into_owned();
+                push(
+                    change.tag(),
+                    DiffLine {
+                        number,
+                        text,
+                        emphasis: vec![],
+                    },
+                );
+            }
+        }
+
+        // Equal lines carry the number of the new input, fix the old side.
+        if op.tag() == DiffTag::Equal {
+            let start = op.old_range().start;
+            for (i, line) in old_lines.iter_mut().enumerate() {
+                line.number = start + i + 1;
+            }
+            blocks.push(DiffBlock::Equal {
+                old: old_lines,
+                new: new_lines,
+            });
+        } else if let Some(DiffBlock::Change { removed, added }) = blocks.last_mut() {
+            removed.append(&mut old_lines);
+            added.append(&mut new_lines);
+        } else {
+            blocks.push(DiffBlock::Change {
+                removed: old_lines,
+                added: new_lines,
+            });
+        }
+    }
+
+    blocks
+}
+
+/// Determines which lines of an `Equal` block are shown when only `context`
+/// lines around changes should be visible.
+pub fn visible_lines(
+    length: usize,
+    context: Option<usize>,
+    is_first: bool,
+    is_last: bool,
+) -> impl Fn(usize) -> bool {
+    move |index| match context {
+        None => true,
+        Some(context) => (!is_first && index < context) || (!is_last && index + context >= length),
+    }
+}
+
+#[cfg(test)]
+fn line(number: usize, text: &str) -> DiffLine {
+    DiffLine {
+        number,
+        text: text.to_string(),
+        emphasis: vec![],
+    }
+}
+
+#[cfg(test)]
+fn emphasized(number: usize, text: &str, emphasis: Range<usize>) -> DiffLine {
+    DiffLine {
+        emphasis: vec![emphasis],
+        ..line(number, text)
+    }
+}
+
+#[test]
+fn test_compute_diff() {
+    let blocks = compute_diff("a\nb\nc\n", "a\nB\nc\nd\n", false);
+
+    assert_eq!(
+        vec![
+            DiffBlock::Equal {
+                old: vec![line(1, "a\n")],
+                new: vec![line(1, "a\n")],
+            },
+            DiffBlock::Change {
+                removed: vec![line(2, "b\n")],
+                added: vec![line(2, "B\n")],
+            },
+            DiffBlock::Equal {
+                old: vec![line(3, "c\n")],
+                new: vec![line(3, "c\n")],
+            },
+            DiffBlock::Change {
+                removed: vec![],
+                added: vec![line(4, "d\n")],
+            },
+        ],
+        blocks
+    );
+}
+
+#[test]
+fn test_compute_word_diff() {
+    let blocks = compute_diff("let x = 1;\n", "let y = 1;\n", true);
+
+    assert_eq!(
+        vec![DiffBlock::Change {
+            removed: vec![emphasized(1, "let x = 1;\n", 4..5)],
+            added: vec![emphasized(1, "let y = 1;\n", 4..5)],
+        }],
+        blocks
+    );
+}
+
+#[test]
+fn test_visible_lines() {
+    let all = visible_lines(10, None, true, true);
+    assert!((0..10).all(&all));
+
+    let middle = visible_lines(10, Some(2), false, false);
+    let shown: Vec<usize> = (0..10).filter(|i| middle(*i)).collect();
+    assert_eq!(vec![0, 1, 8, 9], shown);
+
+    let first = visible_lines(10, Some(2), true, false);
+    let shown: Vec<usize> = (0..10).filter(|i| first(*i)).collect();
+    assert_eq!(vec![8, 9], shown);
+
+    let only = visible_lines(10, Some(3), true, true);
+    assert!((0..10).all(|i| !only(i)));
+}
@@ -0,0 +1,315 @@
+use regex::{Captures, Regex};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Style, Theme};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// A block of another language embedded in an input, like a code fence in
+/// Markdown. The lines between the start and the end of the block are
+/// highlighted with the syntax of the embedded language.
+#[derive(Debug, Clone)]
+pub struct Embedding {
+    /// Matches the line starting the block. The language is taken from the
+    /// capture group `lang`, if there is one.
+    pub start: Regex,
+
+    /// The pattern matching the line ending the block. Named groups of `start`
+    /// can be referenced as `${name}`, e.g. to end a heredoc by its delimiter.
+    pub end: String,
+
+    /// The language of the block if `start` has no `lang` group, or if it
+    /// didn't match.
+    pub language: Option<String>,
+
+    /// Names or file extensions of the syntaxes in which the block can occur.
+    /// Blocks are detected in all syntaxes if this is empty.
+    pub syntaxes: Vec<String>,
+}
+
+impl Embedding {
+    pub fn new<T: Into<String>>(start: Regex, end: T, syntaxes: Vec<String>) -> Embedding {
+        Embedding {
+            start,
+            end: end.into(),
+            language: None,
+            syntaxes,
+        }
+    }
+
+    /// Fenced code blocks in Markdown, like ```` ```rust ````.
+    pub fn fences() -> Embedding {
+        Embedding::new(
+            Regex::new(r"^\s*(?P<fence>`{3,}|~{3,})\s*\{?\.?(?P<lang>[\w+#.-]+)").unwrap(),
+            r"^\s*${fence}[`~]*\s*$",
+            vec!["md".to_string()],
+        )
+    }
+
+    /// Heredocs in Ruby, Perl and PHP whose delimiter names the language,
+    /// like `<<SQL` or `<<~'JSON'`. The delimiter follows `<<` directly and
+    /// starts with a capital letter, so that shifts like `buf << c` aren't
+    /// taken for heredocs.
+    pub fn heredocs() -> Embedding {
+        Embedding::new(
+            Regex::new(r#"<<[-~]?['"]?(?P<delimiter>(?P<lang>[A-Z]\w*))['"]?"#).unwrap(),
+            r"^\s*${delimiter}\s*$",
+            ["rb", "pl", "php"].iter().map(|s| s.to_string()).collect(),
+        )
+    }
+
+    /// Heredocs in shell scripts whose delimiter names the language, like
+    /// `<< 'SQL'`. The delimiter starts with a capital letter, so that shifts
+    /// like `$((x << y))` aren't taken for heredocs.
+    pub fn shell_heredocs() -> Embedding {
+        Embedding::new(
+            Regex::new(r#"<<-?\s*['"]?(?P<delimiter>(?P<lang>[A-Z]\w*))['"]?"#).unwrap(),
+            r"^\s*${delimiter}\s*$",
+            vec!["sh".to_string()],
+        )
+    }
+
+    /// The embeddings detected by default.
+    pub fn builtin() -> Vec<Embedding> {
+        vec![
+            Embedding::fences(),
+            Embedding::heredocs(),
+            Embedding::shell_heredocs(),
+        ]
+    }
+
+    fn applies_to(&self, syntax: &SyntaxReference) -> bool {
+        self.syntaxes.is_empty()
+            || self.syntaxes.iter().any(|name| {
+                name.eq_ignore_ascii_case(&syntax.name) || syntax.file_extensions.contains(name)
+            })
+    }
+
+    /// The pattern for the end of a block, with the references to groups of
+    /// the start replaced by their (escaped) text.
+    fn end_pattern(&self, captures: &Captures) -> Option<Regex> {
+        let mut pattern = self.end.clone();
+        for name in self.start.capture_names().flatten() {
+            let text = captures.name(name).map_or("", |m| m.as_str());
+            pattern = pattern.replace(&format!("${{{}}}", name), &regex::escape(text));
+        }
+        Regex::new(&pattern).ok()
+    }
+}
+
+/// An embedded block which is currently highlighted.
+struct Block<'a> {
+    end: Regex,
+    /// `None` if the language of the block is unknown. The block is still
+    /// tracked, so that its content doesn't start other blocks.
+    highlighter: Option<HighlightLines<'a>>,
+}
+
+/// Highlights blocks of embedded languages within an input.
+pub struct EmbedHighlighter<'a> {
+    embeddings: Vec<Embedding>,
+    theme: &'a Theme,
+    syntax_set: &'a SyntaxSet,
+    block: Option<Block<'a>>,
+}
+
+impl<'a> EmbedHighlighter<'a> {
+    /// Returns `None` if none of the embeddings can occur in `syntax`.
+    pub fn new(
+        embeddings: &[Embedding],
+        syntax: &SyntaxReference,
+        theme: &'a Theme,
+        syntax_set: &'a SyntaxSet,
+    ) -> Option<Self> {
+        let embeddings: Vec<Embedding> = embeddings
+            .iter()
+            .filter(|embedding| embedding.applies_to(syntax))
+            .cloned()
+            .collect();
+        if embeddings.is_empty() {
+            return None;
+        }
+
+        Some(EmbedHighlighter {
+            embeddings,
+            theme,
+            syntax_set,
+            block: None,
+        })
+    }
+
+    /// Whether the current line is within an embedded block.
+    pub fn in_block(&self) -> bool {
+        self.block.is_some()
+    }
+
+    /// Returns the regions of lines within an embedded block of a known
+    /// language, and `None` for all other lines (including the delimiters).
+    pub fn highlight<'l>(&mut self, line: &'l str) -> Option<Vec<(Style, &'l str)>> {
+        if let Some(ref mut block) = self.block {
+            if block.end.is_match(line) {
+                self.block = None;
+                return None;
+            }
+            let highlighter = block.highlighter.as_mut()?;
+            return highlighter.highlight_line(line, self.syntax_set).ok();
+        }
+
+        self.block = self.start_block(line);
+        None
+    }
+
+    /// Follows the start and end of blocks in a line which isn't highlighted,
+    /// e.g. because it's too long.
+    pub fn skip(&mut self, line: &str) {
+        match self.block {
+            Some(ref block) if block.end.is_match(line) => self.block = None,
+            Some(_) => {}
+            None => self.block = self.start_block(line),
+        }
+    }
+
+    /// The block started by a line, if any.
+    fn start_block(&self, line: &str) -> Option<Block<'a>> {
+        self.embeddings.iter().find_map(|embedding| {
+            let captures = embedding.start.captures(line)?;
+            let language = captures
+                .name("lang")
+                .map(|m| m.as_str().to_string())
+                .or_else(|| embedding.language.clone());
+
+            Some(Block {
+                end: embedding.end_pattern(&captures)?,
+                highlighter: language
+                    .and_then(|language| self.find_syntax(&language))
+                    .map(|syntax| HighlightLines::new(syntax, self.theme)),
+            })
+        })
+    }
+
+    fn find_syntax(&self, language: &str) -> Option<&'a SyntaxReference> {
+        self.syntax_set
+            .find_syntax_by_token(&language.to_lowercase())
+            .or_else(|| {
+                self.syntax_set
+                    .syntaxes()
+                    .iter()
+                    .find(|syntax| syntax.name.eq_ignore_ascii_case(language))
+            })
+    }
+}
+
+#[cfg(test)]
+fn syntax_set() -> SyntaxSet {
+    use syntect::parsing::syntax_definition::{Context, SyntaxDefinition};
+    use syntect::parsing::{Scope, SyntaxSetBuilder};
+
+    let mut builder = SyntaxSetBuilder::new();
+    for (name, extension) in &[("Plain Text", "txt"), ("Markdown", "md"), ("Rust", "rs")] {
+        builder.add(SyntaxDefinition {
+            name: name.to_string(),
+            file_extensions: vec![extension.to_string()],
+            scope: Scope::new(&format!("source.{}", extension)).unwrap(),
+            first_line_match: None,
+            hidden: false,
+            variables: Default::default(),
+            contexts: ["__start", "main"]
+                .iter()
+                .map(|name| (name.to_string(), Context::new(false)))
+                .collect(),
+        });
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+fn embedded_lines(embeddings: &[Embedding], syntax: &str, text: &str) -> Vec<bool> {
+    let syntax_set = syntax_set();
+    let theme = Theme::default();
+    let syntax = syntax_set.find_syntax_by_name(syntax).unwrap();
+    let mut highlighter = EmbedHighlighter::new(embeddings, syntax, &theme, &syntax_set).unwrap();
+
+    text.lines()
+        .map(|line| highlighter.highlight(line).is_some())
+        .collect()
+}
+
+#[test]
+fn test_fences() {
+    let markdown = "# Title\n````rust\nfn main() {}\n```\n````\ntext\n~~~ unknown\nx\n~~~\n";
+    assert_eq!(
+        vec![false, false, true, true, false, false, false, false, false],
+        embedded_lines(&[Embedding::fences()], "Markdown", markdown)
+    );
+}
+
+#[test]
+fn test_heredocs() {
+    let mut heredocs = Embedding::heredocs();
+    heredocs.syntaxes.clear();
+
+    let script = "cat <<~'RUST'\n  fn main() {}\n  RUST\necho done\n";
+    assert_eq!(
+        vec![false, true, false, false],
+        embedded_lines(&[heredocs], "Plain Text", script)
+    );
+}
+
+#[test]
+fn test_shifts_are_no_heredocs() {
+    let mut heredocs = Embedding::heredocs();
+    heredocs.syntaxes.clear();
+    let ruby = "buf << c\nsql << r\nx = <<~RUST\n  fn main() {}\nRUST\n";
+    assert_eq!(
+        vec![false, false, false, true, false],
+        embedded_lines(&[heredocs], "Plain Text", ruby)
+    );
+
+    let mut heredocs = Embedding::shell_heredocs();
+    heredocs.syntaxes.clear();
+    let script = "echo $((x << y))\ncat << 'RUST'\nfn main() {}\nRUST\n";
+    assert_eq!(
+        vec![false, false, true, false],
+        embedded_lines(&[heredocs], "Plain Text", script)
+    );
+}
+
+#[test]
+fn test_skipped_lines_end_blocks() {
+    let syntax_set = syntax_set();
+    let theme = Theme::default();
+    let syntax = syntax_set.find_syntax_by_name("Markdown").unwrap();
+    let mut highlighter =
+        EmbedHighlighter::new(&[Embedding::fences()], syntax, &theme, &syntax_set).unwrap();
+
+    assert!(highlighter.highlight("```rust").is_none());
+    assert!(highlighter.in_block());
+    highlighter.skip("```");
+    assert!(!highlighter.in_block());
+    highlighter.skip("```rust");
+    assert!(highlighter.in_block());
+}
+
+#[test]
+fn test_custom_delimiters() {
+    let mut embedding = Embedding::new(
+        Regex::new(r"^%%begin$").unwrap(),
+        "^%%end$",
+        vec!["txt".to_string()],
+    );
+    embedding.language = Some("rs".to_string());
+
+    assert_eq!(
+        vec![false, true, true, false, false],
+        embedded_lines(&[embedding], "Plain Text", "%%begin\na\nb\n%%end\nc\n")
+    );
+}
+
+#[test]
+fn test_embeddings_apply_to_syntaxes() {
+    let syntax_set = syntax_set();
+    let theme = Theme::default();
+    let rust = syntax_set.find_syntax_by_name("Rust").unwrap();
+    let markdown = syntax_set.find_syntax_by_name("Markdown").unwrap();
+
+    assert!(EmbedHighlighter::new(&Embedding::builtin(), rust, &theme, &syntax_set).is_none());
+    assert!(EmbedHighlighter::new(&Embedding::builtin(), markdown, &theme, &syntax_set).is_some());
+}
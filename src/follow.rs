@@ -0,0 +1,141 @@
+use std::fs::{self, Metadata};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::errors::*;
+
+/// Stops following a file. Clones of a handle can be sent to other threads,
+/// and cancelling any of them stops `PrettyPrint::follow`.
+#[derive(Debug, Clone, Default)]
+pub struct FollowHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl FollowHandle {
+    pub fn new() -> Self {
+        FollowHandle::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// What happened to a followed file while it was read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileChange {
+    /// Nothing, or data was appended
+    Unchanged,
+    /// The file got shorter than what was already read, e.g. by `> file`
+    Truncated,
+    /// Another file was moved to the path, e.g. by log rotation
+    Replaced,
+}
+
+/// Identifies a file, to notice when another file is moved to its path.
+#[cfg(unix)]
+type Identity = (u64, u64);
+#[cfg(not(unix))]
+type Identity = std::time::SystemTime;
+
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<Identity> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(metadata: &Metadata) -> Option<Identity> {
+    metadata.created().ok()
+}
+
+/// Notices when a followed file is truncated or replaced.
+#[derive(Debug)]
+pub struct FileWatcher {
+    path: PathBuf,
+    identity: Option<Identity>,
+}
+
+impl FileWatcher {
+    /// Watches the file at `path`, which should just have been opened.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let identity = identity(&fs::metadata(&path)?);
+        Ok(FileWatcher { path, identity })
+    }
+
+    /// Checks the file after `consumed` bytes of it were read. A replaced
+    /// file is watched from now on.
+    pub fn check(&mut self, consumed: u64) -> FileChange {
+        // The file may be missing for a moment during rotation.
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(_) => return FileChange::Unchanged,
+        };
+
+        let identity = identity(&metadata);
+        if identity != self.identity {
+            self.identity = identity;
+            FileChange::Replaced
+        } else if metadata.len() < consumed {
+            FileChange::Truncated
+        } else {
+            FileChange::Unchanged
+        }
+    }
+}
+
+#[test]
+fn test_follow_handle() {
+    let handle = FollowHandle::new();
+    let clone = handle.clone();
+    assert!(!handle.is_cancelled());
+
+    clone.cancel();
+    assert!(handle.is_cancelled());
+}
+
+#[cfg(test)]
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("prettyprint-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_watcher_truncation() {
+    let path = temp_path("truncated.log");
+    fs::write(&path, "a\nb\n").unwrap();
+    let mut watcher = FileWatcher::new(&path).unwrap();
+    assert_eq!(FileChange::Unchanged, watcher.check(4));
+
+    fs::write(&path, "a\nb\nc\n").unwrap();
+    assert_eq!(FileChange::Unchanged, watcher.check(6));
+
+    fs::write(&path, "").unwrap();
+    assert_eq!(FileChange::Truncated, watcher.check(6));
+    assert_eq!(FileChange::Unchanged, watcher.check(0));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_watcher_replacement() {
+    let path = temp_path("rotated.log");
+    let rotated = temp_path("rotated.log.1");
+    fs::write(&path, "old\n").unwrap();
+    let mut watcher = FileWatcher::new(&path).unwrap();
+
+    fs::rename(&path, &rotated).unwrap();
+    assert_eq!(FileChange::Unchanged, watcher.check(4));
+
+    fs::write(&path, "new file\n").unwrap();
+    assert_eq!(FileChange::Replaced, watcher.check(4));
+    assert_eq!(FileChange::Unchanged, watcher.check(0));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&rotated).unwrap();
+}
@@ -0,0 +1,322 @@
+use regex::Regex;
+
+/// Minimum confidence of a guess to be used for automatic detection.
+pub const MIN_CONFIDENCE: f32 = 0.5;
+
+/// Number of matches per pattern that contribute to the score. This keeps a
+/// single repetitive pattern (like `;` at line ends) from dominating.
+const MAX_MATCHES_PER_PATTERN: usize = 5;
+
+/// The result of `guess_language`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Guess {
+    /// The syntax token of the guessed language (e.g. `"json"`)
+    pub language: String,
+
+    /// A value between 0 and 1 describing how certain the guess is
+    pub confidence: f32,
+}
+
+struct Profile {
+    token: &'static str,
+    patterns: Vec<(Regex, f32)>,
+}
+
+impl Profile {
+    fn new(token: &'static str, patterns: &[(&str, f32)]) -> Self {
+        Profile {
+            token,
+            patterns: patterns
+                .iter()
+                .map(|(pattern, weight)| (Regex::new(pattern).unwrap(), *weight))
+                .collect(),
+        }
+    }
+
+    fn score(&self, input: &str) -> f32 {
+        self.patterns
+            .iter()
+            .map(|(regex, weight)| {
+                let matches = regex.find_iter(input).take(MAX_MATCHES_PER_PATTERN).count();
+                matches as f32 * weight
+            })
+            .sum()
+    }
+}
+
+lazy_static! {
+    static ref PROFILES: Vec<Profile> = vec![
+        Profile::new(
+            "json",
+            &[
+                (r#"\A\s*[\{\[]"#, 1.0),
+                (r#""[^"\n]*"\s*:"#, 1.5),
+                (r#"(?m)[\}\]]\s*\z"#, 1.0),
+                (r#":\s*(true|false|null|-?\d+(\.\d+)?)\s*[,\}]"#, 1.0),
+            ],
+        ),
+        Profile::new(
+            "yaml",
+            &[
+                (r"(?m)\A---\s*$", 3.0),
+                (r"(?m)^\s*[\w.-]+:(\s+[^\s{;=]|\s*$)", 1.0),
+                (r"(?m)^\s*- \S", 0.8),
+                (r"(?m)^\s*[\w.-]+: [|>]-?\s*$", 2.0),
+            ],
+        ),
+        Profile::new(
+            "toml",
+            &[
+                (r"(?m)^\[\[?[\w.-]+\]\]?\s*$", 2.5),
+                (r#"(?m)^[\w.-]+ = ("|'|\d|\[|\{|true|false)"#, 1.0),
+            ],
+        ),
+        Profile::new(
+            "sql",
+            &[
+                (r"(?i)\bselect\b[\s\S]+?\bfrom\b", 3.0),
+                (
+                    r"(?i)\b(insert\s+into|delete\s+from|create\s+table|alter\s+table)\b",
+                    3.0
+                ),
+                (r"(?i)\bupdate\s+\w+\s+set\b", 3.0),
+                (r"(?i)\b(where|join|group\s+by|order\s+by|values)\b", 1.0),
+            ],
+        ),
+        Profile::new(
+            "xml",
+            &[
+                (r"\A\s*<\?xml", 5.0),
+                (r"</[\w:.-]+>", 1.0),
+                (r#"<[\w:.-]+(\s+[\w:.-]+="[^"]*")*\s*/?>"#, 0.5),
+            ],
+        ),
+        Profile::new(
+            "html",
+            &[
+                (r"(?i)\A\s*<!doctype html", 5.0),
+                (
+                    r"(?i)</?(html|head|body|div|span|p|a|ul|li|table|script)\b",
+                    1.0
+                ),
+            ],
+        ),
+        Profile::new(
+            "rs",
+            &[
+                (r"\bfn\s+\w+\s*(<[^>]*>)?\(", 2.0),
+                (r"\blet\s+(mut\s+)?\w+", 1.0),
+                (r"\b(impl|pub|struct|enum|trait|mod|use)\b", 0.8),
+                (r"#!?\[\w+", 1.5),
+                (r"\w::\w", 0.5),
+                (r"&(mut\s+)?(self|str)\b", 1.0),
+                (r"\b\w+!\(", 1.0),
+            ],
+        ),
+        Profile::new(
+            "py",
+            &[
+                (r"(?m)^\s*def\s+\w+\s*\(.*\)\s*(->.*)?:\s*$", 3.0),
+                (r"(?m)^\s*(from\s+[\w.]+\s+)?import\s+\w+", 1.5),
+                (
+                    r"(?m)^\s*(if|elif|else|for|while|with|try|except)\b.*:\s*$",
+                    1.0
+                ),
+                (r"\bself\.", 0.8),
+                (r"\b(None|True|False)\b", 0.8),
+            ],
+        ),
+        Profile::new(
+            "js",
+            &[
+                (r"\bfunction\s*\w*\s*\(", 2.0),
+                (r"\b(const|let|var)\s+\w+\s*=", 1.0),
+                (r"=>", 1.0),
+                (r"\bconsole\.\w+\(", 2.0),
+                (r"\brequire\(|\bexport\s+(default\s+)?", 1.5),
+                (r"===|!==", 1.5),
+            ],
+        ),
+        Profile::new(
+            "sh",
+            &[
+                (r"(?m)^\s*(if|while|for)\b.*;\s*(then|do)\s*$", 2.0),
+                (r"(?m)^\s*(fi|done|esac)\s*$", 2.0),
+                (r"\$\{?\w+\}?", 0.5),
+                (r"(?m)^\s*(echo|export|cd|local)\s", 1.0),
+            ],
+        ),
+        Profile::new(
+            "c",
+            &[
+                (r"(?m)^#include\s*[<\x22]", 3.0),
+                (r"\b(int|void|char)\s+\*?\w+\s*\(", 1.5),
+                (r"\b(printf|malloc|free|sizeof)\s*\(", 1.5),
+            ],
+        ),
+        Profile::new(
+            "go",
+            &[
+                (r"(?m)^package\s+\w+\s*$", 3.0),
+                (r"\bfunc\s+(\(\w+\s+\*?\w+\)\s*)?\w+\(", 2.0),
+                (r":=", 1.0),
+                (r"\bfmt\.\w+\(", 1.5),
+            ],
+        ),
+        Profile::new(
+            "md",
+            &[
+                (r"(?m)^#{1,6} \S", 1.5),
+                (r"(?m)^```", 1.5),
+                (r"\[[^\]\n]+\]\([^)\n]+\)", 1.5),
+                (r"(?m)^\s*[*-] \S", 0.5),
+            ],
+        ),
+        Profile::new(
+            "css",
+            &[
+                (
+                    r"(?m)^\s*[.#]?[\w-]+(\s*[,>+~]?\s*[.#:]?[\w-]+)*\s*\{\s*$",
+                    1.5
+                ),
+                (r"(?m)^\s*[\w-]+\s*:\s*[^;\n]+;\s*$", 1.0),
+            ],
+        ),
+    ];
+}
+
+/// Guesses the language of a piece of text, based on its keywords, tokens
+/// and structure.
+///
+/// Returns `None` if the text doesn't resemble any known language.
+pub fn guess_language(input: &str) -> Option<Guess> {
+    let mut scores: Vec<(&'static str, f32)> = PROFILES
+        .iter()
+        .map(|profile| (profile.token, profile.score(input)))
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+
+    if let Some(bonus) = json_structure_bonus(input) {
+        match scores.iter_mut().find(|(token, _)| *token == "json") {
+            Some((_, score)) => *score += bonus,
+            None => scores.push(("json", bonus)),
+        }
+    }
+
+    let total: f32 = scores.iter().map(|(_, score)| score).sum();
+    let (token, best) = scores
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())?;
+
+    // The share of the best profile, weakened if there is little evidence.
+    let evidence = (best / 6.0).min(1.0);
+    Some(Guess {
+        language: token.to_string(),
+        confidence: best / total * evidence,
+    })
+}
+
+/// Checks if the input consists of balanced brackets outside of strings,
+/// starting with an object or array, like JSON does.
+fn json_structure_bonus(input: &str) -> Option<f32> {
+    let trimmed = input.trim();
+    let opening = trimmed.chars().next()?;
+    let closing = trimmed.chars().last()?;
+    if !matches!((opening, closing), ('{', '}') | ('[', ']')) {
+        return None;
+    }
+
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in trimmed.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => stack.push(c),
+            '}' if stack.pop() != Some('{') => return None,
+            ']' if stack.pop() != Some('[') => return None,
+            _ => {}
+        }
+    }
+
+    if stack.is_empty() && !in_string {
+        Some(5.0)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+fn guess(input: &str) -> Option<String> {
+    guess_language(input)
+        .filter(|guess| guess.confidence >= MIN_CONFIDENCE)
+        .map(|guess| guess.language)
+}
+
+#[test]
+fn test_guess_data_formats() {
+    assert_eq!(
+        Some("json".to_string()),
+        guess(r#"{"id":1,"name":"test","tags":["a","b"],"active":true}"#)
+    );
+    assert_eq!(
+        Some("json".to_string()),
+        guess("[\n  {\"x\": 1},\n  {\"x\": 2}\n]")
+    );
+    assert_eq!(
+        Some("yaml".to_string()),
+        guess("---\nname: test\nitems:\n  - one\n  - two\nnested:\n  key: value\n")
+    );
+    assert_eq!(
+        Some("toml".to_string()),
+        guess("[package]\nname = \"prettyprint\"\nversion = \"0.8.1\"\n\n[dependencies]\nregex = \"1\"\n")
+    );
+    assert_eq!(
+        Some("xml".to_string()),
+        guess("<?xml version=\"1.0\"?>\n<note><to>Tove</to><from>Jani</from></note>")
+    );
+}
+
+#[test]
+fn test_guess_code() {
+    assert_eq!(
+        Some("sql".to_string()),
+        guess("SELECT id, name FROM users WHERE age > 21 ORDER BY name;")
+    );
+    assert_eq!(
+        Some("rs".to_string()),
+        guess("fn main() {\n    let mut x = 5;\n    println!(\"{}\", x);\n}\n")
+    );
+    assert_eq!(
+        Some("py".to_string()),
+        guess("import os\n\ndef main():\n    if True:\n        print(os.getcwd())\n")
+    );
+    assert_eq!(
+        Some("js".to_string()),
+        guess("const add = (a, b) => a + b;\nconsole.log(add(1, 2) === 3);\n")
+    );
+    assert_eq!(
+        Some("go".to_string()),
+        guess("package main\n\nimport \"fmt\"\n\nfunc main() {\n\tx := 1\n\tfmt.Println(x)\n}\n")
+    );
+}
+
+#[test]
+fn test_guess_nothing() {
+    assert_eq!(None, guess_language(""));
+    assert_eq!(None, guess("Hello world, this is just some text."));
+
+    let weak = guess_language("x").map(|guess| guess.confidence);
+    assert!(!weak.is_some_and(|confidence| confidence >= MIN_CONFIDENCE));
+}
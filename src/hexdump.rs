@@ -0,0 +1,185 @@
+use std::io::BufRead;
+
+use ansi_term::Colour::{self, Cyan, Fixed, Green, Purple, Yellow};
+
+use crate::errors::*;
+use crate::inputfile::InputFileReader;
+
+/// How binary inputs are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BinaryMode {
+    /// Only print the header
+    #[default]
+    Skip,
+    /// Print a hex dump, with a row of bytes in place of every line. Line
+    /// ranges select rows.
+    HexDump,
+}
+
+/// The width of the offset shown in front of every row.
+pub const OFFSET_WIDTH: usize = 8;
+
+/// Kinds of bytes which are colored differently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteCategory {
+    Null,
+    AsciiPrintable,
+    AsciiWhitespace,
+    AsciiOther,
+    NonAscii,
+}
+
+impl ByteCategory {
+    pub fn of(byte: u8) -> ByteCategory {
+        match byte {
+            0x00 => ByteCategory::Null,
+            b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c => ByteCategory::AsciiWhitespace,
+            _ if byte.is_ascii_graphic() => ByteCategory::AsciiPrintable,
+            _ if byte.is_ascii() => ByteCategory::AsciiOther,
+            _ => ByteCategory::NonAscii,
+        }
+    }
+
+    fn color(self) -> Colour {
+        match self {
+            ByteCategory::Null => Fixed(242),
+            ByteCategory::AsciiPrintable => Cyan,
+            ByteCategory::AsciiWhitespace => Green,
+            ByteCategory::AsciiOther => Purple,
+            ByteCategory::NonAscii => Yellow,
+        }
+    }
+
+    /// The character shown for a byte in the text column.
+    fn symbol(self, byte: u8) -> char {
+        match self {
+            ByteCategory::Null => '⋄',
+            ByteCategory::AsciiPrintable => byte as char,
+            ByteCategory::AsciiWhitespace => '_',
+            ByteCategory::AsciiOther => '•',
+            ByteCategory::NonAscii => '×',
+        }
+    }
+}
+
+/// Renders the hex and the text column of a row. Rows shorter than
+/// `bytes_per_row` are padded, so that the text columns line up.
+pub fn format_row(bytes: &[u8], bytes_per_row: usize, colored: bool) -> (String, String) {
+    let mut hex = String::new();
+    let mut text = String::new();
+
+    for index in 0..bytes_per_row {
+        if index > 0 {
+            // Groups of eight bytes are separated by an extra space.
+            hex.push_str(if index % 8 == 0 { "  " } else { " " });
+        }
+
+        let byte = match bytes.get(index) {
+            Some(&byte) => byte,
+            None => {
+                hex.push_str("  ");
+                continue;
+            }
+        };
+        let category = ByteCategory::of(byte);
+        let (byte_hex, symbol) = (format!("{:02x}", byte), category.symbol(byte).to_string());
+        if colored {
+            hex.push_str(&category.color().paint(byte_hex).to_string());
+            text.push_str(&category.color().paint(symbol).to_string());
+        } else {
+            hex.push_str(&byte_hex);
+            text.push_str(&symbol);
+        }
+    }
+
+    (hex, text)
+}
+
+/// Splits an input into rows of a fixed number of bytes. The bytes are read as
+/// they are, even if the input has an encoding.
+pub struct RowReader<'a> {
+    reader: Box<dyn BufRead + 'a>,
+    bytes_per_row: usize,
+}
+
+impl<'a> RowReader<'a> {
+    pub fn new(reader: InputFileReader<'a>, bytes_per_row: usize) -> Self {
+        RowReader {
+            reader: reader.into_raw(),
+            bytes_per_row: bytes_per_row.max(1),
+        }
+    }
+
+    /// Reads the next row into `row`. Returns `false` at the end of the input.
+    pub fn read_row(&mut self, row: &mut Vec<u8>) -> Result<bool> {
+        row.clear();
+        while row.len() < self.bytes_per_row {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            let length = buffer.len().min(self.bytes_per_row - row.len());
+            row.extend_from_slice(&buffer[..length]);
+            self.reader.consume(length);
+        }
+        Ok(!row.is_empty())
+    }
+}
+
+#[test]
+fn test_byte_categories() {
+    assert_eq!(ByteCategory::Null, ByteCategory::of(0));
+    assert_eq!(ByteCategory::AsciiPrintable, ByteCategory::of(b'a'));
+    assert_eq!(ByteCategory::AsciiWhitespace, ByteCategory::of(b'\n'));
+    assert_eq!(ByteCategory::AsciiOther, ByteCategory::of(0x7f));
+    assert_eq!(ByteCategory::NonAscii, ByteCategory::of(0xff));
+}
+
+#[test]
+fn test_format_row() {
+    let (hex, text) = format_row(b"\x7fELF\x02\x01\x01\x00\x00 ", 16, false);
+    assert_eq!("7f 45 4c 46 02 01 01 00  00 20                  ", hex);
+    assert_eq!("•ELF•••⋄⋄_", text);
+    assert_eq!(16 * 3, hex.len());
+}
+
+#[cfg(test)]
+fn read_rows(reader: InputFileReader, bytes_per_row: usize) -> Vec<Vec<u8>> {
+    let mut rows = RowReader::new(reader, bytes_per_row);
+    let mut row = Vec::new();
+    let mut all = Vec::new();
+    while rows.read_row(&mut row).unwrap() {
+        all.push(row.clone());
+    }
+    all
+}
+
+#[test]
+fn test_row_reader() {
+    let content = b"line one\nline two\n\x00\x01";
+    assert_eq!(
+        vec![
+            b"line one".to_vec(),
+            b"\nline tw".to_vec(),
+            b"o\n\x00\x01".to_vec()
+        ],
+        read_rows(InputFileReader::new(&content[..]), 8)
+    );
+
+    // Rows show the bytes of the input, not the decoded text.
+    let content = b"\x61\x00\x0A\x00\x62\x00";
+    let mut reader = InputFileReader::new(&content[..]);
+    reader.set_encoding(Some(encoding::all::UTF_16LE));
+    assert_eq!(
+        vec![b"\x61\x00\x0A\x00".to_vec(), b"\x62\x00".to_vec()],
+        read_rows(reader, 4)
+    );
+}
+
+#[test]
+fn test_row_reader_without_newlines() {
+    let content = vec![0; 4 * 1024 * 1024 + 3];
+    let rows = read_rows(InputFileReader::new(&content[..]), 16);
+    assert_eq!(content.len().div_ceil(16), rows.len());
+    assert_eq!(vec![0; 3], rows[rows.len() - 1]);
+}
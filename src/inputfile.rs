@@ -1,48 +1,250 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::mem;
 
 use content_inspector::{self, ContentType};
+use encoding::{EncodingRef, RawDecoder};
 
+use crate::charset;
 use crate::errors::*;
+use crate::line_ending::LineEnding;
+use crate::whitespace::{detect_indent_width, IndentStyle};
 
+const BYTE_ORDER_MARK: &[u8] = "\u{FEFF}".as_bytes();
+
+/// The most bytes which are read from the input at once.
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reads an input line by line. Inputs which aren't UTF-8 are decoded while
+/// they are read, so lines are split on decoded characters and always
+/// returned as UTF-8.
 pub struct InputFileReader<'a> {
     inner: Box<dyn BufRead + 'a>,
+    decoder: Option<Box<dyn RawDecoder>>,
+    /// Text which was read (and decoded), of which the part from `cursor`
+    /// isn't returned yet
+    pending: Vec<u8>,
+    cursor: usize,
+    /// The length of the unreturned text which is known to contain no line
+    /// ending
+    searched: usize,
+    /// The bytes read before the first line was returned, to decode them
+    /// again if the encoding changes
+    raw: Option<Vec<u8>>,
+    /// The number of bytes consumed from `inner`
+    position: u64,
     pub first_line: Vec<u8>,
     pub content_type: ContentType,
+    /// The encoding of the input, or `None` for UTF-8
+    pub encoding: Option<EncodingRef>,
 }
 
 impl<'a> InputFileReader<'a> {
-    fn new<R: BufRead + 'a>(mut reader: R) -> InputFileReader<'a> {
-        let mut first_line = vec![];
-        reader.read_until(b'\n', &mut first_line).ok();
+    pub fn new<R: BufRead + 'a>(mut reader: R) -> InputFileReader<'a> {
+        let content_type = match reader.fill_buf() {
+            Ok(buffer) => {
+                let end = buffer
+                    .iter()
+                    .position(|&byte| byte == b'\n')
+                    .map_or(buffer.len(), |newline| newline + 1);
+                content_inspector::inspect(&buffer[..end])
+            }
+            Err(_) => ContentType::UTF_8,
+        };
 
-        let content_type = content_inspector::inspect(&first_line[..]);
+        let mut input = InputFileReader {
+            inner: Box::new(reader),
+            decoder: None,
+            pending: vec![],
+            cursor: 0,
+            searched: 0,
+            raw: Some(vec![]),
+            position: 0,
+            first_line: vec![],
+            content_type,
+            encoding: None,
+        };
+        input.set_encoding(charset::from_content_type(content_type));
+        input
+    }
 
-        if content_type == ContentType::UTF_16LE {
-            reader.read_until(0x00, &mut first_line).ok();
+    /// Decodes the input from `encoding`, or as UTF-8 if it's `None`. This
+    /// has no effect once the first line was read.
+    pub fn set_encoding(&mut self, encoding: Option<EncodingRef>) {
+        let raw = match self.raw.take() {
+            Some(raw) => raw,
+            None => return,
+        };
+        let inner = mem::replace(&mut self.inner, Box::new(io::empty()));
+        self.inner = Box::new(Cursor::new(raw).chain(inner));
+        self.raw = Some(vec![]);
+        self.position = 0;
+
+        self.encoding = encoding.filter(|encoding| encoding.name() != "utf-8");
+        self.decoder = self.encoding.map(|encoding| encoding.raw_decoder());
+        self.pending.clear();
+        self.cursor = 0;
+        self.searched = 0;
+
+        let mut first_line = vec![];
+        self.read_next_line(&mut first_line).ok();
+        if self.content_type.is_text() && first_line.starts_with(BYTE_ORDER_MARK) {
+            first_line.drain(..BYTE_ORDER_MARK.len());
         }
+        self.first_line = first_line;
+    }
 
-        InputFileReader {
-            inner: Box::new(reader),
-            first_line,
-            content_type,
+    /// The beginning of the input as it was read, e.g. to detect its
+    /// encoding. Nothing is consumed.
+    pub fn sample(&mut self) -> Vec<u8> {
+        let mut sample = self.raw.clone().unwrap_or_default();
+        if let Ok(buffered) = self.inner.fill_buf() {
+            sample.extend_from_slice(buffered);
         }
+        sample
+    }
+
+    /// The number of bytes of the input which were read so far, before they
+    /// were decoded. Text which isn't returned yet is included.
+    pub fn position(&self) -> u64 {
+        self.position
     }
 
     pub fn read_line(&mut self, buf: &mut Vec<u8>) -> io::Result<bool> {
+        self.raw = None;
         if self.first_line.is_empty() {
-            let res = self.inner.read_until(b'\n', buf).map(|size| size > 0)?;
-
-            if self.content_type == ContentType::UTF_16LE {
-                self.inner.read_until(0x00, buf).ok();
-            }
-
-            Ok(res)
+            self.read_next_line(buf)
         } else {
             buf.append(&mut self.first_line);
             Ok(true)
         }
     }
+
+    /// The decoded text at the beginning of the input, e.g. to guess its
+    /// language. Nothing is consumed.
+    pub fn text_sample(&self) -> String {
+        String::from_utf8_lossy(&self.head()).into_owned()
+    }
+
+    /// The input as it is, without decoding it. It starts at the beginning of
+    /// the input, unless lines were read already.
+    pub fn into_raw(self) -> Box<dyn BufRead + 'a> {
+        match self.raw {
+            Some(raw) => Box::new(Cursor::new(raw).chain(self.inner)),
+            None => self.inner,
+        }
+    }
+
+    /// Detects the style of the line endings at the beginning of the input.
+    pub fn line_ending(&self) -> Option<LineEnding> {
+        LineEnding::detect(&self.head())
+    }
+
+    /// Detects the indentation at the beginning of the input.
+    pub fn indent_style(&self) -> Option<IndentStyle> {
+        IndentStyle::detect(&String::from_utf8_lossy(&self.head()))
+    }
+
+    /// Detects the width of an indentation level at the beginning of the
+    /// input, if it's indented with spaces.
+    pub fn indent_width(&self) -> Option<usize> {
+        detect_indent_width(&String::from_utf8_lossy(&self.head()))
+    }
+
+    /// The decoded text which was read but isn't returned yet.
+    fn head(&self) -> Vec<u8> {
+        let mut head = self.first_line.clone();
+        head.extend_from_slice(&self.pending[self.cursor..]);
+        head
+    }
+
+    /// Reads the next line, which ends with a LF, a CRLF or a lone CR.
+    fn read_next_line(&mut self, buf: &mut Vec<u8>) -> io::Result<bool> {
+        loop {
+            let unread = &self.pending[self.cursor..];
+            if let Some(length) = line_length(unread, self.searched) {
+                buf.extend_from_slice(&unread[..length]);
+                self.cursor += length;
+                self.searched = 0;
+                return Ok(true);
+            }
+            // Only a CR at the end may still become part of a line ending.
+            self.searched = unread.len().saturating_sub(1);
+
+            if !self.read_chunk()? {
+                let found = self.cursor < self.pending.len();
+                buf.extend_from_slice(&self.pending[self.cursor..]);
+                self.pending.clear();
+                self.cursor = 0;
+                self.searched = 0;
+                return Ok(found);
+            }
+        }
+    }
+
+    /// Reads the next chunk of the input into `pending`, decoding it and
+    /// replacing invalid sequences if the input isn't UTF-8. Returns `false`
+    /// at the end of the input.
+    fn read_chunk(&mut self) -> io::Result<bool> {
+        // Returned text is only removed once it makes up most of `pending`,
+        // so that the rest isn't moved for every line.
+        if self.cursor > 0 && self.cursor >= self.pending.len() / 2 {
+            self.pending.drain(..self.cursor);
+            self.cursor = 0;
+        }
+
+        // Readers of slices return all of their contents at once.
+        let chunk = self.inner.fill_buf()?;
+        let chunk = &chunk[..chunk.len().min(MAX_CHUNK_SIZE)];
+        let mut decoded = String::new();
+
+        let consumed = match self.decoder.as_mut() {
+            None => {
+                self.pending.extend_from_slice(chunk);
+                chunk.len()
+            }
+            Some(decoder) if chunk.is_empty() => {
+                // The input may end in the middle of a character.
+                if decoder.raw_finish(&mut decoded).is_some() {
+                    decoded.push(char::REPLACEMENT_CHARACTER);
+                }
+                0
+            }
+            Some(decoder) => match decoder.raw_feed(chunk, &mut decoded) {
+                (_, Some(error)) => {
+                    decoded.push(char::REPLACEMENT_CHARACTER);
+                    error.upto.max(0) as usize
+                }
+                (_, None) => chunk.len(),
+            },
+        };
+        let more = !chunk.is_empty();
+
+        if let Some(raw) = self.raw.as_mut() {
+            raw.extend_from_slice(&chunk[..consumed]);
+        }
+        self.inner.consume(consumed);
+        self.position += consumed as u64;
+        self.pending.extend_from_slice(decoded.as_bytes());
+        Ok(more)
+    }
+}
+
+/// The length of the first line in `text`, including its line ending, or
+/// `None` if it doesn't end yet. A CR at the end may be followed by a LF, so
+/// the line doesn't end there yet. The first `searched` bytes are known to
+/// contain no line ending.
+fn line_length(text: &[u8], searched: usize) -> Option<usize> {
+    let end = searched
+        + text[searched..]
+            .iter()
+            .position(|&byte| byte == b'\n' || byte == b'\r')?;
+    match (text[end], text.get(end + 1)) {
+        (b'\n', _) => Some(end + 1),
+        (_, Some(b'\n')) => Some(end + 2),
+        (_, Some(_)) => Some(end + 1),
+        (_, None) => None,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +270,21 @@ impl InputFile {
             _ => unimplemented!(), // Used to be InputFile::Stdin
         }
     }
+
+    /// Reads the whole input into a string, replacing invalid UTF-8.
+    pub fn get_contents(&self) -> Result<String> {
+        let bytes = match self {
+            InputFile::Ordinary(filename) => fs::read(filename)?,
+            InputFile::String(s) => return Ok(s.clone()),
+            InputFile::StdIn => {
+                let mut bytes = vec![];
+                io::stdin().read_to_end(&mut bytes)?;
+                bytes
+            }
+        };
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
 }
 
 #[test]
@@ -99,31 +316,131 @@ fn basic() {
     assert!(buffer.is_empty());
 }
 
+#[cfg(test)]
+fn read_lines(reader: &mut InputFileReader) -> Vec<String> {
+    let mut lines = vec![];
+    let mut buffer = vec![];
+    while reader.read_line(&mut buffer).unwrap() {
+        lines.push(String::from_utf8(buffer.clone()).unwrap());
+        buffer.clear();
+    }
+    lines
+}
+
 #[test]
 fn utf16le() {
     let content = b"\xFF\xFE\x73\x00\x0A\x00\x64\x00";
     let mut reader = InputFileReader::new(&content[..]);
 
-    assert_eq!(b"\xFF\xFE\x73\x00\x0A\x00", &reader.first_line[..]);
+    assert_eq!(ContentType::UTF_16LE, reader.content_type);
+    assert_eq!(b"s\n", &reader.first_line[..]);
+    assert_eq!(vec!["s\n", "d"], read_lines(&mut reader));
+}
 
-    let mut buffer = vec![];
+#[test]
+fn utf16_code_units_with_newline_bytes() {
+    // U+0A0D and U+010A contain the byte of `\n`.
+    let le = b"\xFF\xFE\x0D\x0A\x0A\x01\x0A\x00\x61\x00";
+    assert_eq!(
+        vec!["\u{0A0D}\u{010A}\n", "a"],
+        read_lines(&mut InputFileReader::new(&le[..]))
+    );
 
-    let res = reader.read_line(&mut buffer);
-    assert!(res.is_ok());
-    assert_eq!(true, res.unwrap());
-    assert_eq!(b"\xFF\xFE\x73\x00\x0A\x00", &buffer[..]);
+    let be = b"\xFE\xFF\x0A\x0D\x01\x0A\x00\x0A\x00\x61";
+    let mut reader = InputFileReader::new(&be[..]);
+    assert_eq!(ContentType::UTF_16BE, reader.content_type);
+    assert_eq!(vec!["\u{0A0D}\u{010A}\n", "a"], read_lines(&mut reader));
+}
 
-    buffer.clear();
+#[test]
+fn utf16_odd_length() {
+    let content = b"\xFF\xFE\x61\x00\x0A\x00\x62\x00\x63";
+    assert_eq!(
+        vec!["a\n", "b\u{FFFD}"],
+        read_lines(&mut InputFileReader::new(&content[..]))
+    );
+}
 
-    let res = reader.read_line(&mut buffer);
-    assert!(res.is_ok());
-    assert_eq!(true, res.unwrap());
-    assert_eq!(b"\x64\x00", &buffer[..]);
+#[test]
+fn byte_order_marks() {
+    let content = b"\xEF\xBB\xBFfn main() {}\n";
+    let mut reader = InputFileReader::new(&content[..]);
+    assert_eq!(b"fn main() {}\n", &reader.first_line[..]);
+    assert_eq!(vec!["fn main() {}\n"], read_lines(&mut reader));
+}
 
-    buffer.clear();
+#[test]
+fn explicit_encoding() {
+    use encoding::all::{UTF_16LE, WINDOWS_31J};
 
-    let res = reader.read_line(&mut buffer);
-    assert!(res.is_ok());
-    assert_eq!(false, res.unwrap());
-    assert!(buffer.is_empty());
+    // UTF-16 without a byte order mark looks like binary data.
+    let content = b"\x61\x00\x0A\x00\x62\x00";
+    let mut reader = InputFileReader::new(&content[..]);
+    assert_eq!(ContentType::BINARY, reader.content_type);
+    reader.set_encoding(Some(UTF_16LE));
+    assert_eq!(vec!["a\n", "b"], read_lines(&mut reader));
+
+    // A second byte of Shift_JIS is never a newline, but it may be a
+    // backslash.
+    let content = b"\x83\x5C\n\x93\xfa\n";
+    let mut reader = InputFileReader::new(&content[..]);
+    assert_eq!(b"\x83\x5C\n", &reader.first_line[..]);
+    reader.set_encoding(Some(WINDOWS_31J));
+    assert_eq!(b"\xE3\x82\xBD\n", &reader.first_line[..]);
+    assert_eq!(vec!["ソ\n", "日\n"], read_lines(&mut reader));
+}
+
+#[test]
+fn line_endings() {
+    let content = b"a\r\nb\rc\nd";
+    let mut reader = InputFileReader::new(&content[..]);
+    assert_eq!(Some(LineEnding::Mixed), reader.line_ending());
+    assert_eq!(vec!["a\r\n", "b\r", "c\n", "d"], read_lines(&mut reader));
+
+    // A CRLF may be split between two reads.
+    let content = b"ab\r\ncd\r";
+    let mut reader = InputFileReader::new(BufReader::with_capacity(3, &content[..]));
+    assert_eq!(vec!["ab\r\n", "cd\r"], read_lines(&mut reader));
+
+    let mut content = vec![0xFF, 0xFE];
+    content.extend("a\r\nb\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+    let mut reader = InputFileReader::new(&content[..]);
+    assert_eq!(Some(LineEnding::CrLf), reader.line_ending());
+    assert_eq!(vec!["a\r\n", "b\r\n"], read_lines(&mut reader));
+}
+
+#[test]
+fn long_inputs() {
+    // A CRLF at the end of the first chunk, and a line across chunks
+    let mut content = vec![b'a'; MAX_CHUNK_SIZE - 1];
+    content.extend_from_slice(b"\r\nb\n");
+    content.extend(vec![b'c'; MAX_CHUNK_SIZE * 2]);
+    content.extend_from_slice(b"\nd");
+
+    let lines = read_lines(&mut InputFileReader::new(&content[..]));
+    let lengths: Vec<_> = lines.iter().map(String::len).collect();
+    assert_eq!(
+        vec![MAX_CHUNK_SIZE + 1, 2, MAX_CHUNK_SIZE * 2 + 1, 1],
+        lengths
+    );
+    assert!(lines[0].ends_with("a\r\n"));
+}
+
+#[test]
+fn positions() {
+    use encoding::all::WINDOWS_31J;
+
+    // Decoded text is longer than the Shift_JIS input, and shorter than the
+    // UTF-16 input.
+    let content = b"\x93\xfa\x96\x7b\n";
+    let mut reader = InputFileReader::new(&content[..]);
+    reader.set_encoding(Some(WINDOWS_31J));
+    assert_eq!(vec!["日本\n"], read_lines(&mut reader));
+    assert_eq!(5, reader.position());
+
+    let mut content = vec![0xFF, 0xFE];
+    content.extend("ab\n".encode_utf16().flat_map(u16::to_le_bytes));
+    let mut reader = InputFileReader::new(&content[..]);
+    assert_eq!(vec!["ab\n"], read_lines(&mut reader));
+    assert_eq!(8, reader.position());
 }
@@ -19,28 +19,71 @@ extern crate console;
 extern crate content_inspector;
 extern crate directories;
 extern crate encoding;
+extern crate globset;
+extern crate regex;
 extern crate shell_words;
+extern crate similar;
 extern crate syntect;
 
 mod assets;
+mod audit;
+mod batch;
+mod brackets;
 mod builder;
+mod charset;
+mod checkpoint;
 mod decorations;
+mod detection;
+mod diff;
 mod dirs;
+mod embed;
+mod follow;
+#[cfg(feature = "guess")]
+mod guess;
+mod hexdump;
 mod inputfile;
+mod line_ending;
 mod line_range;
+mod log;
 mod output;
+mod patch;
 mod preprocessor;
 mod printer;
+#[cfg(feature = "reformat")]
+mod reformat;
+mod safeguard;
+mod scopes;
+mod search;
+mod selector;
 mod style;
 mod syntax_mapping;
 mod terminal;
+#[cfg(feature = "serde")]
+mod value;
+mod whitespace;
 
+pub use crate::audit::{Finding, FindingKind};
 pub use crate::builder::{PagingMode, PrettyPrint, PrettyPrinter};
+pub use crate::diff::DiffMode;
+pub use crate::embed::Embedding;
+pub use crate::follow::FollowHandle;
+#[cfg(feature = "guess")]
+pub use crate::guess::{guess_language, Guess};
+pub use crate::hexdump::BinaryMode;
+pub use crate::line_ending::LineEndingMode;
+pub use crate::line_range::{LineRange, LineRanges};
+pub use crate::safeguard::{LongLines, PrintReport};
+pub use crate::selector::Selector;
+pub use crate::syntax_mapping::{MappingTarget, SyntaxMapping};
+#[cfg(feature = "serde")]
+pub use crate::value::Format;
+pub use crate::whitespace::IndentGuides;
 
 #[allow(deprecated)] // remove it after error-chain/issues/254 resolved 🤗
 mod errors {
     error_chain! {
         foreign_links {
+            Builder(crate::builder::PrettyPrinterError);
             Clap(::clap::Error);
             Io(::std::io::Error);
             SyntectError(::syntect::LoadingError);
@@ -140,6 +183,190 @@ mod tests {
         printer.file("fixtures/fib.rs").unwrap();
     }
 
+    #[test]
+    fn it_can_print_debug_values() {
+        let printer = PrettyPrinter::default().build().unwrap();
+        printer.debug(&Some((1, "two", 3.0))).unwrap();
+        printer.debug(&vec![Some("a"), None]).unwrap();
+    }
+
+    #[test]
+    fn it_can_print_diffs() {
+        let old = "fn main() {\n    println!(\"Hello\");\n}\n";
+        let new = "fn main() {\n    println!(\"Hello, world\");\n}\n";
+
+        for mode in &[DiffMode::Unified, DiffMode::SideBySide] {
+            let printer = PrettyPrinter::default()
+                .language("rust")
+                .diff_mode(*mode)
+                .diff_context(1)
+                .word_diff(true)
+                .build()
+                .unwrap();
+            printer.diff(old, new).unwrap();
+        }
+    }
+
+    #[test]
+    fn it_can_highlight_matches() {
+        let printer = PrettyPrinter::default()
+            .language("rust")
+            .highlight_pattern(regex::Regex::new(r"fn \w+").unwrap())
+            .highlight_literals(vec!["println".to_string()])
+            .match_context(1)
+            .build()
+            .unwrap();
+        printer.file("fixtures/fib.rs").unwrap();
+    }
+
+    #[test]
+    fn it_can_select_functions() {
+        let printer = PrettyPrinter::default()
+            .selectors(vec![Selector::function("fib")])
+            .build()
+            .unwrap();
+        printer.file("fixtures/fib.rs").unwrap();
+    }
+
+    #[test]
+    fn it_can_highlight_code_fences() {
+        let printer = PrettyPrinter::default()
+            .language("markdown")
+            .build()
+            .unwrap();
+        printer
+            .string("# Example\n\n```rust\nfn main() {}\n```\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_can_highlight_logs() {
+        let printer = PrettyPrinter::default().language("log").build().unwrap();
+        printer
+            .string(
+                "2024-03-01T12:00:00Z INFO started port=8080 (src/main.rs:42)\n\
+                 2024-03-01T12:00:01Z ERROR failed {\"user\": 1}\n",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn it_can_print_multiple_files() {
+        let printer = PrettyPrinter::default()
+            .paging_mode(PagingMode::Never)
+            .threads(2)
+            .build()
+            .unwrap();
+        printer
+            .files(vec!["fixtures/fib.rs", "Cargo.toml", "README.md"])
+            .unwrap();
+        assert!(printer
+            .files(vec!["fixtures/fib.rs", "missing.rs"])
+            .is_err());
+    }
+
+    #[test]
+    fn it_can_follow_files() {
+        use std::io::Write;
+        use std::time::Duration;
+
+        let path =
+            std::env::temp_dir().join(format!("prettyprint-{}-follow.log", std::process::id()));
+        std::fs::write(&path, "first line\n").unwrap();
+
+        // Following stops on its own after the fifth line.
+        let printer = PrettyPrinter::default()
+            .paging_mode(PagingMode::Never)
+            .follow_interval(Duration::from_millis(10))
+            .max_input_lines(5usize)
+            .build()
+            .unwrap();
+        let handle = FollowHandle::new();
+
+        let writer = {
+            let (path, handle) = (path.clone(), handle.clone());
+            std::thread::spawn(move || {
+                let mut file = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .unwrap();
+                for i in 0..3 {
+                    std::thread::sleep(Duration::from_millis(20));
+                    writeln!(file, "appended line {}", i).unwrap();
+                }
+                std::thread::sleep(Duration::from_millis(50));
+                std::fs::write(&path, "after truncation\nlast line\n").unwrap();
+                std::thread::sleep(Duration::from_millis(500));
+                handle.cancel();
+            })
+        };
+
+        printer.follow(path.to_string_lossy(), &handle).unwrap();
+        writer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Four lines of the first file and the first line after the
+        // truncation were printed.
+        assert_eq!(Some(1), printer.last_report().truncated_after);
+    }
+
+    #[test]
+    fn it_can_print_hex_dumps() {
+        let path = std::env::temp_dir().join("prettyprint-hexdump.bin");
+        std::fs::write(&path, b"\x7fELF\x02\x01\x01\x00\x00binary\ncontent").unwrap();
+
+        let printer = PrettyPrinter::default()
+            .paging_mode(PagingMode::Never)
+            .binary_mode(BinaryMode::HexDump)
+            .hex_bytes_per_row(8usize)
+            .line_ranges(LineRanges::from(vec![LineRange::from("2:").unwrap()]))
+            .build()
+            .unwrap();
+        printer.file(path.to_str().unwrap()).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn it_can_audit_suspicious_content() {
+        let printer = PrettyPrinter::default()
+            .paging_mode(PagingMode::Never)
+            .language("rust")
+            .audit(true)
+            .build()
+            .unwrap();
+        printer
+            .string("let pаssword = 1;\n/*\u{202E} } \u{2066}if admin\u{2069} */\n")
+            .unwrap();
+
+        let findings = printer.last_report().findings;
+        assert_eq!(4, findings.len());
+        assert_eq!(
+            (1, 6, 'а', FindingKind::Homoglyph),
+            (
+                findings[0].line,
+                findings[0].column,
+                findings[0].character,
+                findings[0].kind
+            )
+        );
+        assert!(findings[1..]
+            .iter()
+            .all(|finding| finding.line == 2 && finding.kind == FindingKind::BidiControl));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_can_print_serializable_values() {
+        let mut value = std::collections::BTreeMap::new();
+        value.insert("numbers", vec![1, 2, 3]);
+
+        let printer = PrettyPrinter::default().build().unwrap();
+        for format in &[Format::Json, Format::Yaml, Format::Toml, Format::Ron] {
+            printer.value(&value, *format).unwrap();
+        }
+    }
+
     /// Show available syntax highlighting themes
     #[test]
     fn show_themes() {
@@ -147,5 +374,4 @@ mod tests {
         assert!(printer.get_themes().len() > 0);
         println!("{:?}", printer.get_themes().keys());
     }
-
 }
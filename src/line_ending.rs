@@ -0,0 +1,121 @@
+/// The style of the line endings of an input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    /// More than one of the other styles
+    Mixed,
+}
+
+impl LineEnding {
+    /// Detects the style of the terminated lines in `text`. Returns `None`
+    /// if there are none.
+    pub fn detect(text: &[u8]) -> Option<LineEnding> {
+        let (mut lf, mut crlf, mut cr) = (false, false, false);
+        let mut bytes = text.iter().enumerate().peekable();
+        while let Some((index, &byte)) = bytes.next() {
+            match byte {
+                b'\n' => lf = true,
+                b'\r' if bytes.peek().is_some_and(|&(_, &next)| next == b'\n') => {
+                    bytes.next();
+                    crlf = true;
+                }
+                // A CR at the end may be followed by a LF which isn't there yet.
+                b'\r' if index + 1 < text.len() => cr = true,
+                _ => {}
+            }
+        }
+
+        match (lf, crlf, cr) {
+            (false, false, false) => None,
+            (true, false, false) => Some(LineEnding::Lf),
+            (false, true, false) => Some(LineEnding::CrLf),
+            (false, false, true) => Some(LineEnding::Cr),
+            _ => Some(LineEnding::Mixed),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+            LineEnding::Mixed => "MIXED LINE ENDINGS",
+        }
+    }
+}
+
+/// How the line endings of inputs are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineEndingMode {
+    /// Print the line endings of the input. Lone CRs are followed by a LF, so
+    /// that lines don't overwrite each other.
+    #[default]
+    Keep,
+    /// Print all line endings as LF
+    Lf,
+    /// Print all line endings as CRLF
+    CrLf,
+}
+
+/// Splits a line into its content and its line ending, which is empty for
+/// the last line of an input without a final newline.
+pub fn split_line_ending(line: &str) -> (&str, &str) {
+    let content = if let Some(content) = line.strip_suffix("\r\n") {
+        content
+    } else {
+        line.strip_suffix(['\n', '\r']).unwrap_or(line)
+    };
+    line.split_at(content.len())
+}
+
+/// Replaces the line ending of `line` according to `mode`.
+pub fn normalize_line_ending(line: &mut String, mode: LineEndingMode) {
+    let (content, ending) = split_line_ending(line);
+    let replacement = match (mode, ending) {
+        (_, "") => return,
+        (LineEndingMode::Keep, "\r") => "\r\n",
+        (LineEndingMode::Keep, _) => return,
+        (LineEndingMode::Lf, _) => "\n",
+        (LineEndingMode::CrLf, _) => "\r\n",
+    };
+    line.truncate(content.len());
+    line.push_str(replacement);
+}
+
+#[test]
+fn test_detect() {
+    assert_eq!(None, LineEnding::detect(b"no newline"));
+    assert_eq!(Some(LineEnding::Lf), LineEnding::detect(b"a\nb\n"));
+    assert_eq!(Some(LineEnding::CrLf), LineEnding::detect(b"a\r\nb\r\nc"));
+    assert_eq!(Some(LineEnding::Cr), LineEnding::detect(b"a\rb\r"));
+    assert_eq!(Some(LineEnding::Mixed), LineEnding::detect(b"a\r\nb\nc"));
+    // The CR at the end may be the beginning of a CRLF.
+    assert_eq!(Some(LineEnding::CrLf), LineEnding::detect(b"a\r\nb\r"));
+}
+
+#[test]
+fn test_split_line_ending() {
+    assert_eq!(("a", "\r\n"), split_line_ending("a\r\n"));
+    assert_eq!(("a", "\n"), split_line_ending("a\n"));
+    assert_eq!(("a", "\r"), split_line_ending("a\r"));
+    assert_eq!(("a", ""), split_line_ending("a"));
+    assert_eq!(("a\r", "\r\n"), split_line_ending("a\r\r\n"));
+}
+
+#[test]
+fn test_normalize_line_ending() {
+    let normalized = |line: &str, mode| {
+        let mut line = line.to_string();
+        normalize_line_ending(&mut line, mode);
+        line
+    };
+
+    assert_eq!("a\r\n", normalized("a\r\n", LineEndingMode::Keep));
+    assert_eq!("a\r\n", normalized("a\r", LineEndingMode::Keep));
+    assert_eq!("a\n", normalized("a\r\n", LineEndingMode::Lf));
+    assert_eq!("a\n", normalized("a\r", LineEndingMode::Lf));
+    assert_eq!("a\r\n", normalized("a\n", LineEndingMode::CrLf));
+    assert_eq!("a", normalized("a", LineEndingMode::CrLf));
+}
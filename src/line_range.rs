@@ -1,9 +1,15 @@
 use crate::errors::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LineRange {
     pub lower: usize,
     pub upper: usize,
+
+    /// Shown in the separator before the range
+    pub name: Option<String>,
+
+    /// The number of lines at the end of the input, for `-n:`
+    from_end: Option<usize>,
 }
 
 impl Default for LineRange {
@@ -11,6 +17,8 @@ impl Default for LineRange {
         LineRange {
             lower: usize::min_value(),
             upper: usize::max_value(),
+            name: None,
+            from_end: None,
         }
     }
 }
@@ -24,9 +32,35 @@ impl LineRange {
         LineRange::default()
     }
 
+    /// Parses a line range. Supported are single lines (`40`), ranges
+    /// (`40:50`, `:50`, `40:`), a number of lines after a line (`40:+10`),
+    /// the last lines of the input (`-10:`) and a line with context around it
+    /// (`40::3`). Ranges can be named with a `name=` prefix.
     pub fn parse_range(range_raw: &str) -> Result<LineRange> {
         let mut new_range = LineRange::new();
 
+        let range_raw = match range_raw.split_once('=') {
+            Some(("", _)) => return Err("Empty line range name".into()),
+            Some((name, range)) => {
+                new_range.name = Some(name.to_string());
+                range
+            }
+            None => range_raw,
+        };
+
+        if let Some((line, context)) = range_raw.split_once("::") {
+            let line: usize = line.parse()?;
+            let context: usize = context.parse()?;
+            new_range.lower = line.saturating_sub(context);
+            new_range.upper = line.saturating_add(context);
+            return Ok(new_range);
+        }
+
+        if range_raw.starts_with('-') && range_raw.ends_with(':') {
+            new_range.from_end = Some(range_raw[1..range_raw.len() - 1].parse()?);
+            return Ok(new_range);
+        }
+
         if range_raw.bytes().nth(0).ok_or("Empty line range")? == b':' {
             new_range.upper = range_raw[1..].parse()?;
             return Ok(new_range);
@@ -36,9 +70,17 @@ impl LineRange {
         }
 
         let line_numbers: Vec<&str> = range_raw.split(':').collect();
+        if line_numbers.len() == 1 {
+            new_range.lower = line_numbers[0].parse()?;
+            new_range.upper = new_range.lower;
+            return Ok(new_range);
+        }
         if line_numbers.len() == 2 {
             new_range.lower = line_numbers[0].parse()?;
-            new_range.upper = line_numbers[1].parse()?;
+            new_range.upper = match line_numbers[1].strip_prefix('+') {
+                Some(count) => new_range.lower.saturating_add(count.parse()?),
+                None => line_numbers[1].parse()?,
+            };
             return Ok(new_range);
         }
 
@@ -46,7 +88,20 @@ impl LineRange {
     }
 
     pub fn is_inside(&self, line: usize) -> bool {
-        line >= self.lower && line <= self.upper
+        self.from_end.is_none() && line >= self.lower && line <= self.upper
+    }
+
+    /// Turns a range relative to the end of the input into an absolute one.
+    pub fn resolve(&self, line_count: usize) -> LineRange {
+        match self.from_end {
+            Some(count) => LineRange {
+                lower: line_count.saturating_sub(count) + 1,
+                upper: usize::MAX,
+                name: self.name.clone(),
+                from_end: None,
+            },
+            None => self.clone(),
+        }
     }
 }
 
@@ -71,15 +126,65 @@ fn test_parse_partial_max() {
     assert_eq!(usize::max_value(), range.upper);
 }
 
+#[test]
+fn test_parse_single_line() {
+    let range = LineRange::from("40").expect("Shouldn't fail on test!");
+    assert_eq!(40, range.lower);
+    assert_eq!(40, range.upper);
+}
+
+#[test]
+fn test_parse_relative() {
+    let range = LineRange::from("40:+10").expect("Shouldn't fail on test!");
+    assert_eq!(40, range.lower);
+    assert_eq!(50, range.upper);
+}
+
+#[test]
+fn test_parse_context() {
+    let range = LineRange::from("40::3").expect("Shouldn't fail on test!");
+    assert_eq!(37, range.lower);
+    assert_eq!(43, range.upper);
+
+    let range = LineRange::from("2::5").expect("Shouldn't fail on test!");
+    assert_eq!(0, range.lower);
+    assert_eq!(7, range.upper);
+}
+
+#[test]
+fn test_parse_from_end() {
+    let range = LineRange::from("-10:").expect("Shouldn't fail on test!");
+    assert!(!range.is_inside(95));
+
+    let range = range.resolve(100);
+    assert_eq!(91, range.lower);
+    assert_eq!(usize::MAX, range.upper);
+    assert!(range.is_inside(95));
+
+    assert_eq!(1, LineRange::from("-10:").unwrap().resolve(5).lower);
+}
+
+#[test]
+fn test_parse_named() {
+    let range = LineRange::from("setup=10:20").expect("Shouldn't fail on test!");
+    assert_eq!(Some("setup".to_string()), range.name);
+    assert_eq!(10, range.lower);
+    assert_eq!(20, range.upper);
+}
+
 #[test]
 fn test_parse_fail() {
     let range = LineRange::from("40:50:80");
     assert!(range.is_err());
-    let range = LineRange::from("40::80");
-    assert!(range.is_err());
     let range = LineRange::from(":40:");
     assert!(range.is_err());
-    let range = LineRange::from("40");
+    let range = LineRange::from("40:+");
+    assert!(range.is_err());
+    let range = LineRange::from("-:");
+    assert!(range.is_err());
+    let range = LineRange::from("=40:50");
+    assert!(range.is_err());
+    let range = LineRange::from("");
     assert!(range.is_err());
 }
 
@@ -111,7 +216,27 @@ impl Default for LineRanges {
 }
 
 impl LineRanges {
+    /// Creates the line ranges, merging overlapping and adjacent ones with the
+    /// same name. Ranges with different names are kept apart, so that each
+    /// line keeps the name of its range.
     pub fn from(ranges: Vec<LineRange>) -> LineRanges {
+        let (mut relative, mut absolute): (Vec<_>, Vec<_>) =
+            ranges.into_iter().partition(|r| r.from_end.is_some());
+        absolute.sort_by_key(|r| r.lower);
+
+        let mut ranges: Vec<LineRange> = Vec::with_capacity(absolute.len());
+        for range in absolute {
+            match ranges.last_mut() {
+                Some(last)
+                    if range.lower <= last.upper.saturating_add(1) && range.name == last.name =>
+                {
+                    last.upper = last.upper.max(range.upper);
+                }
+                _ => ranges.push(range),
+            }
+        }
+        ranges.append(&mut relative);
+
         let largest_upper_bound = ranges
             .iter()
             .map(|r| r.upper)
@@ -123,6 +248,45 @@ impl LineRanges {
         }
     }
 
+    /// Adds more ranges. If all lines were selected before, only the new
+    /// ranges are selected afterwards.
+    pub fn with(&self, ranges: Vec<LineRange>) -> LineRanges {
+        if ranges.is_empty() {
+            return self.clone();
+        }
+
+        let mut all = if self.ranges == vec![LineRange::default()] {
+            vec![]
+        } else {
+            self.ranges.clone()
+        };
+        all.extend(ranges);
+        LineRanges::from(all)
+    }
+
+    /// Whether some ranges are relative to the end of the input.
+    pub fn needs_line_count(&self) -> bool {
+        self.ranges.iter().any(|r| r.from_end.is_some())
+    }
+
+    /// Resolves all ranges relative to the end of the input.
+    pub fn resolve(&self, line_count: usize) -> LineRanges {
+        LineRanges::from(self.ranges.iter().map(|r| r.resolve(line_count)).collect())
+    }
+
+    /// The first line within any of the ranges.
+    pub fn first_line(&self) -> usize {
+        self.ranges.iter().map(|r| r.lower).min().unwrap_or(0)
+    }
+
+    /// The name of a range containing the line, if one of them has a name.
+    pub fn name_at(&self, line: usize) -> Option<&str> {
+        self.ranges
+            .iter()
+            .filter(|r| r.is_inside(line))
+            .find_map(|r| r.name.as_deref())
+    }
+
     pub fn check(&self, line: usize) -> RangeCheckResult {
         if self.ranges.is_empty() {
             RangeCheckResult::InRange
@@ -193,3 +357,60 @@ fn test_ranges_empty() {
 
     assert_eq!(RangeCheckResult::InRange, ranges.check(1));
 }
+
+#[test]
+fn test_ranges_merged() {
+    let ranges = ranges(&["10:20", "21:25", "1:2", "b=40:50", "b=45:60"]);
+
+    assert_eq!(
+        vec![(1, 2), (10, 25), (40, 60)],
+        ranges
+            .ranges
+            .iter()
+            .map(|r| (r.lower, r.upper))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some("b"), ranges.name_at(55));
+    assert_eq!(None, ranges.name_at(1));
+}
+
+#[test]
+fn test_named_ranges_are_not_merged() {
+    let ranges = ranges(&["10:20", "a=15:30", "31:35", "b=33:40"]);
+
+    assert_eq!(None, ranges.name_at(12));
+    assert_eq!(Some("a"), ranges.name_at(15));
+    assert_eq!(Some("a"), ranges.name_at(25));
+    assert_eq!(None, ranges.name_at(31));
+    assert_eq!(Some("b"), ranges.name_at(34));
+    assert_eq!(RangeCheckResult::InRange, ranges.check(32));
+    assert_eq!(RangeCheckResult::AfterLastRange, ranges.check(41));
+}
+
+#[test]
+fn test_ranges_from_end() {
+    let ranges = ranges(&["1:2", "-3:"]);
+    assert!(ranges.needs_line_count());
+    assert_eq!(RangeCheckResult::OutsideRange, ranges.check(9));
+
+    let ranges = ranges.resolve(10);
+    assert!(!ranges.needs_line_count());
+    assert_eq!(RangeCheckResult::InRange, ranges.check(2));
+    assert_eq!(RangeCheckResult::OutsideRange, ranges.check(7));
+    assert_eq!(RangeCheckResult::InRange, ranges.check(8));
+    assert_eq!(RangeCheckResult::InRange, ranges.check(10));
+}
+
+#[test]
+fn test_ranges_with() {
+    let selected = vec![LineRange::from("5:6").unwrap()];
+
+    let all = LineRanges::default().with(selected.clone());
+    assert_eq!(RangeCheckResult::OutsideRange, all.check(1));
+    assert_eq!(RangeCheckResult::InRange, all.check(5));
+
+    let ranges = ranges(&["1:2"]).with(selected);
+    assert_eq!(RangeCheckResult::InRange, ranges.check(1));
+    assert_eq!(RangeCheckResult::OutsideRange, ranges.check(3));
+    assert_eq!(RangeCheckResult::InRange, ranges.check(6));
+}
@@ -0,0 +1,393 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Highlighter, Style, Theme};
+use syntect::parsing::{ScopeStack, SyntaxReference, SyntaxSet};
+
+use crate::inputfile::InputFile;
+
+lazy_static! {
+    static ref TIMESTAMP: Regex = Regex::new(concat!(
+        // RFC 3339, with a space instead of the `T` as well
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+        // Common log format, e.g. `10/Oct/2000:13:55:36 -0700`
+        r"|\d{2}/[A-Z][a-z]{2}/\d{4}:\d{2}:\d{2}:\d{2}(?: [+-]\d{4})?",
+        // Syslog, e.g. `Oct 11 22:14:15`
+        r"|\b[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}\b",
+    ))
+    .unwrap();
+    static ref LEVEL: Regex = Regex::new(
+        r"\b(?:FATAL|CRITICAL|ERROR|ERR|WARNING|WARN|INFO|NOTICE|DEBUG|TRACE)\b"
+    )
+    .unwrap();
+    static ref KEY_VALUE: Regex =
+        Regex::new(r#"\b([A-Za-z_][\w.\-]*)(=)("(?:[^"\\]|\\.)*"|[^\s"]+)"#).unwrap();
+    static ref LOCATION: Regex =
+        Regex::new(r"(?:[\w.\-]+/)*[\w\-]+\.[A-Za-z]\w*:\d+(?::\d+)?\b").unwrap();
+    static ref NUMBER: Regex = Regex::new(r"^-?\d+(?:\.\d+)?(?:ms|s|us|µs|ns)?$").unwrap();
+}
+
+/// The severity of a log record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// Parses level names like `WARN` or `error`.
+    pub fn parse(name: &str) -> Option<LogLevel> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "FATAL" | "CRITICAL" | "ERROR" | "ERR" => LogLevel::Error,
+            "WARNING" | "WARN" => LogLevel::Warning,
+            "INFO" | "NOTICE" => LogLevel::Info,
+            "DEBUG" => LogLevel::Debug,
+            "TRACE" => LogLevel::Trace,
+            _ => return None,
+        })
+    }
+}
+
+/// A part of a log line with a special meaning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogToken {
+    Timestamp,
+    Level(LogLevel),
+    /// The key of a `key=value` pair
+    Key,
+    /// The `=` of a `key=value` pair
+    Operator,
+    Number,
+    Value,
+    /// A reference to a source file, like `main.rs:42`
+    Location,
+    /// A JSON object, either the whole line or a payload at its end
+    Json,
+}
+
+impl LogToken {
+    const ALL: [LogToken; 12] = [
+        LogToken::Timestamp,
+        LogToken::Level(LogLevel::Error),
+        LogToken::Level(LogLevel::Warning),
+        LogToken::Level(LogLevel::Info),
+        LogToken::Level(LogLevel::Debug),
+        LogToken::Level(LogLevel::Trace),
+        LogToken::Key,
+        LogToken::Operator,
+        LogToken::Number,
+        LogToken::Value,
+        LogToken::Location,
+        LogToken::Json,
+    ];
+
+    /// The scopes used to look up the style of the token in the theme. Inner
+    /// scopes take precedence if the theme has a style for them.
+    fn scopes(self) -> &'static str {
+        match self {
+            LogToken::Timestamp => "comment.log.timestamp",
+            LogToken::Level(LogLevel::Error) => "markup.deleted.log.error",
+            LogToken::Level(LogLevel::Warning) => "markup.changed.log.warning",
+            LogToken::Level(LogLevel::Info) => "markup.inserted.log.info",
+            LogToken::Level(LogLevel::Debug) => "constant.language.log.debug",
+            LogToken::Level(LogLevel::Trace) => "comment.log.trace",
+            LogToken::Key => "entity.other.attribute-name.log",
+            LogToken::Operator => "keyword.operator.assignment.log",
+            LogToken::Number => "constant.numeric.log",
+            LogToken::Value => "string.unquoted.log",
+            LogToken::Location => "entity.name.function.log markup.underline.link.log",
+            LogToken::Json => "meta.structure.dictionary.json.log",
+        }
+    }
+}
+
+/// Checks if an input should be highlighted as a log: if the language is
+/// `log`, or if a plain text input is named `*.log` or starts with a
+/// timestamp.
+pub fn is_log(
+    language: Option<&str>,
+    syntax_name: &str,
+    file: &InputFile,
+    first_line: &[u8],
+) -> bool {
+    if let Some(language) = language {
+        return language.eq_ignore_ascii_case("log");
+    }
+    if syntax_name != "Plain Text" {
+        return false;
+    }
+
+    let named_log = match file {
+        InputFile::Ordinary(filename) => Path::new(filename)
+            .extension()
+            .is_some_and(|extension| extension == "log"),
+        _ => false,
+    };
+    let first_line = String::from_utf8_lossy(first_line);
+    named_log
+        || TIMESTAMP
+            .find(first_line.trim_start_matches('['))
+            .is_some_and(|m| m.start() == 0)
+}
+
+/// Finds the tokens of a log line. The returned ranges are sorted and don't
+/// overlap.
+pub fn tokenize(line: &str) -> Vec<(LogToken, Range<usize>)> {
+    // Accepted tokens never overlap, so they are kept ordered by their start
+    // and only the last one starting before a new range can overlap it.
+    let mut tokens: BTreeMap<usize, (LogToken, Range<usize>)> = BTreeMap::new();
+    let mut add = |token: LogToken, range: Range<usize>| {
+        let overlaps = tokens
+            .range(..range.end)
+            .next_back()
+            .is_some_and(|(_, (_, other))| range.start < other.end);
+        if !overlaps && !range.is_empty() {
+            tokens.insert(range.start, (token, range));
+        }
+    };
+
+    if let Some(range) = json_payload(line) {
+        add(LogToken::Json, range);
+    }
+
+    for captures in KEY_VALUE.captures_iter(line) {
+        let (key, operator, value) = match (captures.get(1), captures.get(2), captures.get(3)) {
+            (Some(key), Some(operator), Some(value)) => (key, operator, value),
+            _ => continue,
+        };
+        add(LogToken::Key, key.range());
+        add(LogToken::Operator, operator.range());
+        add(classify_value(key.as_str(), value.as_str()), value.range());
+    }
+
+    for m in TIMESTAMP.find_iter(line) {
+        add(LogToken::Timestamp, m.range());
+    }
+    for m in LOCATION.find_iter(line) {
+        add(LogToken::Location, m.range());
+    }
+    for m in LEVEL.find_iter(line) {
+        if let Some(level) = LogLevel::parse(m.as_str()) {
+            add(LogToken::Level(level), m.range());
+        }
+    }
+
+    tokens.into_values().collect()
+}
+
+/// Finds a JSON object at the end of a line, e.g. a whole JSON record or the
+/// payload after a message.
+fn json_payload(line: &str) -> Option<Range<usize>> {
+    let end = line.trim_end().len();
+    if !line[..end].ends_with('}') {
+        return None;
+    }
+
+    let start = line.find('{')?;
+    // Values of `key={...}` pairs are left to the key-value tokens.
+    if line[..start].ends_with('=') {
+        return None;
+    }
+    Some(start..end)
+}
+
+fn classify_value(key: &str, value: &str) -> LogToken {
+    let key = key.to_ascii_lowercase();
+    let level = LogLevel::parse(value.trim_matches('"'));
+    match level {
+        Some(level) if ["level", "lvl", "severity", "loglevel"].contains(&key.as_str()) => {
+            LogToken::Level(level)
+        }
+        _ if TIMESTAMP
+            .find(value)
+            .is_some_and(|m| m.range() == (0..value.len())) =>
+        {
+            LogToken::Timestamp
+        }
+        _ if LOCATION
+            .find(value)
+            .is_some_and(|m| m.range() == (0..value.len())) =>
+        {
+            LogToken::Location
+        }
+        _ if NUMBER.is_match(value) => LogToken::Number,
+        _ => LogToken::Value,
+    }
+}
+
+/// Highlights log lines by the scopes of their tokens, and JSON payloads in
+/// the JSON syntax.
+pub struct LogHighlighter<'a> {
+    theme: &'a Theme,
+    json: Option<&'a SyntaxReference>,
+    plain: Style,
+    /// The style of every token in the theme
+    styles: Vec<(LogToken, Style)>,
+}
+
+impl<'a> LogHighlighter<'a> {
+    /// With `highlight_json`, JSON records and payloads are highlighted with
+    /// the JSON syntax of the syntax set, if there is one.
+    pub fn new(theme: &'a Theme, syntax_set: &'a SyntaxSet, highlight_json: bool) -> Self {
+        let json = syntax_set
+            .find_syntax_by_name("JSON")
+            .filter(|_| highlight_json);
+
+        let highlighter = Highlighter::new(theme);
+        let plain = highlighter.get_default();
+        let styles = LogToken::ALL
+            .iter()
+            .map(|&token| {
+                let style = ScopeStack::from_str(token.scopes())
+                    .map(|stack| highlighter.style_for_stack(stack.as_slice()))
+                    .unwrap_or(plain);
+                (token, style)
+            })
+            .collect();
+
+        LogHighlighter {
+            theme,
+            json,
+            plain,
+            styles,
+        }
+    }
+
+    fn style(&self, token: LogToken) -> Style {
+        self.styles
+            .iter()
+            .find(|(other, _)| *other == token)
+            .map_or(self.plain, |(_, style)| *style)
+    }
+
+    pub fn highlight<'l>(&self, line: &'l str, syntax_set: &SyntaxSet) -> Vec<(Style, &'l str)> {
+        let plain = self.plain;
+        let mut regions = Vec::new();
+        let mut cursor = 0;
+        for (token, range) in tokenize(line) {
+            if cursor < range.start {
+                regions.push((plain, &line[cursor..range.start]));
+            }
+
+            let text = &line[range.clone()];
+            match (token, self.json) {
+                (LogToken::Json, Some(json)) => {
+                    // Payloads are independent of each other, so every one is
+                    // highlighted from scratch.
+                    let mut json = HighlightLines::new(json, self.theme);
+                    match json.highlight_line(text, syntax_set) {
+                        Ok(json_regions) => regions.extend(json_regions),
+                        Err(_) => regions.push((self.style(token), text)),
+                    }
+                }
+                _ => regions.push((self.style(token), text)),
+            }
+            cursor = range.end;
+        }
+        if cursor < line.len() {
+            regions.push((plain, &line[cursor..]));
+        }
+
+        regions
+    }
+}
+
+#[cfg(test)]
+fn kinds(line: &str) -> Vec<(LogToken, &str)> {
+    tokenize(line)
+        .into_iter()
+        .map(|(token, range)| (token, &line[range]))
+        .collect()
+}
+
+#[test]
+fn test_tokenize_levels_and_timestamps() {
+    use self::LogToken::*;
+
+    assert_eq!(
+        vec![
+            (Timestamp, "2024-03-01T12:00:00.123Z"),
+            (Level(LogLevel::Warning), "WARN"),
+            (Location, "src/main.rs:42"),
+        ],
+        kinds("2024-03-01T12:00:00.123Z WARN disk almost full (src/main.rs:42)\n")
+    );
+    assert_eq!(
+        vec![
+            (Timestamp, "Oct 11 22:14:15"),
+            (Level(LogLevel::Error), "ERROR")
+        ],
+        kinds("Oct 11 22:14:15 host app[42]: ERROR: failed")
+    );
+    // Words which only contain a level name aren't levels.
+    assert!(kinds("INFORMATION ERRORS").is_empty());
+}
+
+#[test]
+fn test_tokenize_key_values() {
+    use self::LogToken::*;
+
+    assert_eq!(
+        vec![
+            (Key, "level"),
+            (Operator, "="),
+            (Level(LogLevel::Error), "error"),
+            (Key, "msg"),
+            (Operator, "="),
+            (Value, "\"can't connect\""),
+            (Key, "took"),
+            (Operator, "="),
+            (Number, "12ms"),
+            (Key, "ts"),
+            (Operator, "="),
+            (Timestamp, "2024-03-01T12:00:00Z"),
+        ],
+        kinds("level=error msg=\"can't connect\" took=12ms ts=2024-03-01T12:00:00Z")
+    );
+}
+
+#[test]
+fn test_tokenize_json() {
+    use self::LogToken::*;
+
+    let record = r#"{"level":"info","msg":"started"}"#;
+    assert_eq!(vec![(Json, record)], kinds(record));
+    assert_eq!(
+        vec![
+            (Timestamp, "2024-03-01 12:00:00"),
+            (Level(LogLevel::Info), "INFO"),
+            (Json, r#"{"user": 1}"#),
+        ],
+        kinds(r#"2024-03-01 12:00:00 INFO request {"user": 1}"#)
+    );
+}
+
+#[test]
+fn test_is_log() {
+    let file = |name: &str| InputFile::Ordinary(name.to_string());
+
+    assert!(is_log(Some("log"), "Plain Text", &file("x"), b""));
+    assert!(!is_log(Some("rust"), "Rust", &file("x.log"), b""));
+    assert!(is_log(None, "Plain Text", &file("app.log"), b"hello"));
+    assert!(is_log(
+        None,
+        "Plain Text",
+        &file("output"),
+        b"[2024-03-01T12:00:00Z] started"
+    ));
+    assert!(!is_log(None, "Plain Text", &file("notes.txt"), b"hello"));
+    assert!(!is_log(
+        None,
+        "Rust",
+        &file("main.rs"),
+        b"2024-03-01T12:00:00Z"
+    ));
+}
@@ -0,0 +1,270 @@
+use std::path::Path;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Style, Theme};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::assets::HighlightingAssets;
+use crate::diff::LineChange;
+use crate::syntax_mapping::SyntaxMapping;
+
+/// Checks if an input looks like a unified diff, either by its syntax or by
+/// the header `git diff` writes.
+pub fn is_patch(syntax_name: &str, first_line: &[u8]) -> bool {
+    syntax_name == "Diff" || first_line.starts_with(b"diff --git ")
+}
+
+/// A line of a unified diff.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchLine<'l> {
+    /// The `+++` header, with the path of the new file. The path is `None`
+    /// for deleted files.
+    NewFile(Option<&'l str>),
+    /// The `---` header, with the path of the old file
+    OldFile(Option<&'l str>),
+    /// A `@@ -1,2 +1,3 @@` header
+    Hunk,
+    /// A line within a hunk, without its marker
+    Content(Option<LineChange>, &'l str),
+    /// Anything else, e.g. `diff --git` or `index` lines
+    Other,
+}
+
+/// Splits a unified diff into its lines. It keeps track of the hunk sizes,
+/// so that removed lines starting with `--` aren't mistaken for headers.
+#[derive(Debug, Default)]
+pub struct PatchParser {
+    old_remaining: usize,
+    new_remaining: usize,
+}
+
+impl PatchParser {
+    pub fn parse<'l>(&mut self, line: &'l str) -> PatchLine<'l> {
+        if self.old_remaining > 0 || self.new_remaining > 0 {
+            let (change, text) = match line.chars().next() {
+                Some('+') => (Some(LineChange::Added), &line[1..]),
+                Some('-') => (Some(LineChange::Removed), &line[1..]),
+                Some(' ') => (None, &line[1..]),
+                // Empty context lines lose their space in some editors.
+                Some('\n') | Some('\r') | None => (None, line),
+                // "\ No newline at end of file"
+                Some('\\') => return PatchLine::Other,
+                Some(_) => {
+                    self.old_remaining = 0;
+                    self.new_remaining = 0;
+                    return self.parse(line);
+                }
+            };
+
+            if change != Some(LineChange::Added) {
+                self.old_remaining = self.old_remaining.saturating_sub(1);
+            }
+            if change != Some(LineChange::Removed) {
+                self.new_remaining = self.new_remaining.saturating_sub(1);
+            }
+            return PatchLine::Content(change, text);
+        }
+
+        if let Some(path) = line.strip_prefix("+++ ") {
+            PatchLine::NewFile(header_path(path, "b/"))
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            PatchLine::OldFile(header_path(path, "a/"))
+        } else if let Some((old, new)) = hunk_sizes(line) {
+            self.old_remaining = old;
+            self.new_remaining = new;
+            PatchLine::Hunk
+        } else {
+            PatchLine::Other
+        }
+    }
+}
+
+/// Extracts the path from a `---` or `+++` header, without the timestamp
+/// `diff -u` appends and without the prefix `git diff` adds.
+fn header_path<'l>(header: &'l str, prefix: &str) -> Option<&'l str> {
+    let path = header.split('\t').next().unwrap_or("").trim_end();
+    if path.is_empty() || path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path))
+}
+
+/// Parses the number of old and new lines from a hunk header.
+fn hunk_sizes(line: &str) -> Option<(usize, usize)> {
+    let mut parts = line.strip_prefix("@@ -")?.split_whitespace();
+    let old = parts.next()?;
+    let new = parts.next()?.strip_prefix('+')?;
+
+    let size = |range: &str| match range.split_once(',') {
+        Some((_, size)) => size.parse().ok(),
+        None => range.parse::<usize>().ok().map(|_| 1),
+    };
+    Some((size(old)?, size(new)?))
+}
+
+/// The change of a line within a hunk and the highlighted regions of its
+/// content.
+pub type HunkContent<'l> = (Option<LineChange>, Vec<(Style, &'l str)>);
+
+/// Highlights the content of hunks in the language of the patched file.
+///
+/// Old and new lines are highlighted separately, so that the state of both
+/// versions of the file stays consistent (e.g. within comments).
+pub struct PatchHighlighter<'a> {
+    assets: &'a HighlightingAssets,
+    theme: &'a Theme,
+    syntax_mapping: SyntaxMapping,
+    parser: PatchParser,
+    old_path: Option<String>,
+    old: Option<HighlightLines<'a>>,
+    new: Option<HighlightLines<'a>>,
+}
+
+impl<'a> PatchHighlighter<'a> {
+    pub fn new(
+        assets: &'a HighlightingAssets,
+        theme: &'a Theme,
+        syntax_mapping: SyntaxMapping,
+    ) -> Self {
+        PatchHighlighter {
+            assets,
+            theme,
+            syntax_mapping,
+            parser: PatchParser::default(),
+            old_path: None,
+            old: None,
+            new: None,
+        }
+    }
+
+    /// Highlights a line of the patch. For lines within a hunk, this returns
+    /// the change and the regions of the content, without the marker.
+    /// Returns `None` for all other lines, or if the language of the file is
+    /// unknown.
+    pub fn highlight<'l>(
+        &mut self,
+        line: &'l str,
+        syntax_set: &SyntaxSet,
+    ) -> Option<HunkContent<'l>> {
+        match self.parser.parse(line) {
+            PatchLine::OldFile(path) => {
+                self.old_path = path.map(str::to_string);
+                None
+            }
+            PatchLine::NewFile(path) => {
+                // Deleted files are highlighted based on their old path.
+                let path = path.map(str::to_string).or_else(|| self.old_path.take());
+                let syntax = path.and_then(|path| self.get_syntax(&path));
+                self.old = syntax.map(|syntax| HighlightLines::new(syntax, self.theme));
+                self.new = syntax.map(|syntax| HighlightLines::new(syntax, self.theme));
+                None
+            }
+            PatchLine::Content(change, text) => {
+                let old = self.old.as_mut()?;
+                let new = self.new.as_mut()?;
+
+                let regions = match change {
+                    Some(LineChange::Added) => new.highlight_line(text, syntax_set),
+                    Some(LineChange::Removed) => old.highlight_line(text, syntax_set),
+                    None => old
+                        .highlight_line(text, syntax_set)
+                        .and_then(|_| new.highlight_line(text, syntax_set)),
+                };
+                Some((change, regions.ok()?))
+            }
+            PatchLine::Hunk | PatchLine::Other => None,
+        }
+    }
+
+    /// The paths come from the patch, so only their names are used, and the
+    /// files they name aren't read.
+    fn get_syntax(&self, path: &str) -> Option<&'a SyntaxReference> {
+        let syntax = self
+            .assets
+            .get_path_syntax(Path::new(path), &self.syntax_mapping)?;
+
+        if syntax.name == self.assets.syntax_set.find_syntax_plain_text().name {
+            return None;
+        }
+        Some(syntax)
+    }
+}
+
+#[cfg(test)]
+fn parse_all(patch: &str) -> Vec<PatchLine<'_>> {
+    let mut parser = PatchParser::default();
+    patch.lines().map(|line| parser.parse(line)).collect()
+}
+
+#[test]
+fn test_parse_git_diff() {
+    let patch = "diff --git a/src/main.rs b/src/main.rs
+index 3b18e51..a042389 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+--- removed;
++    println!(\"hi\");
+ }
+";
+
+    assert_eq!(
+        vec![
+            PatchLine::Other,
+            PatchLine::Other,
+            PatchLine::OldFile(Some("src/main.rs")),
+            PatchLine::NewFile(Some("src/main.rs")),
+            PatchLine::Hunk,
+            PatchLine::Content(None, "fn main() {"),
+            PatchLine::Content(Some(LineChange::Removed), "-- removed;"),
+            PatchLine::Content(Some(LineChange::Added), "    println!(\"hi\");"),
+            PatchLine::Content(None, "}"),
+        ],
+        parse_all(patch)
+    );
+}
+
+#[test]
+fn test_parse_multiple_files() {
+    let patch = "--- old.py\t2024-01-01 10:00:00
++++ new.py\t2024-01-02 10:00:00
+@@ -1 +1 @@
+-x = 1
++x = 2
+--- /dev/null
++++ b/new.c
+@@ -0,0 +1 @@
++int x;
+";
+
+    assert_eq!(
+        vec![
+            PatchLine::OldFile(Some("old.py")),
+            PatchLine::NewFile(Some("new.py")),
+            PatchLine::Hunk,
+            PatchLine::Content(Some(LineChange::Removed), "x = 1"),
+            PatchLine::Content(Some(LineChange::Added), "x = 2"),
+            PatchLine::OldFile(None),
+            PatchLine::NewFile(Some("new.c")),
+            PatchLine::Hunk,
+            PatchLine::Content(Some(LineChange::Added), "int x;"),
+        ],
+        parse_all(patch)
+    );
+}
+
+#[test]
+fn test_hunk_sizes() {
+    assert_eq!(Some((3, 4)), hunk_sizes("@@ -1,3 +1,4 @@ fn main() {"));
+    assert_eq!(Some((1, 0)), hunk_sizes("@@ -5 +4,0 @@"));
+    assert_eq!(None, hunk_sizes("@@@ -1,3 -1,3 +1,4 @@@"));
+    assert_eq!(None, hunk_sizes("@@ invalid @@"));
+}
+
+#[test]
+fn test_is_patch() {
+    assert!(is_patch("Diff", b""));
+    assert!(is_patch("Plain Text", b"diff --git a/x b/x\n"));
+    assert!(!is_patch("Rust", b"fn main() {}\n"));
+}
@@ -1,4 +1,17 @@
+use std::ops::Range;
+
 use console::AnsiCodeIterator;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// Characters which aren't visible, except for ASCII ones and the
+    /// no-break space.
+    static ref INVISIBLE: Regex = Regex::new(r"^[\p{Cc}\p{Cf}\p{Zs}\p{Zl}\p{Zp}]$").unwrap();
+}
+
+/// The width of tabs which are shown although tabs aren't expanded.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
 
 /// Expand tabs like an ANSI-enabled expand(1).
 pub fn expand_tabs(line: &str, width: usize, cursor: &mut usize) -> String {
@@ -33,15 +46,39 @@ pub fn expand_tabs(line: &str, width: usize, cursor: &mut usize) -> String {
     buffer
 }
 
-pub fn replace_nonprintable(input: &str, tab_width: usize) -> String {
+/// Replaces invisible characters by visible glyphs: whitespace and line
+/// endings by symbols, control characters by control pictures, and others by
+/// `<U+XXXX>` escapes. With `suspicious_only`, only characters which may
+/// hide or reorder code are replaced, i.e. not spaces, tabs and line endings.
+///
+/// Returns the ranges of the glyphs in the output, and whether they replace
+/// a suspicious character.
+pub fn replace_nonprintable(
+    input: &str,
+    tab_width: usize,
+    suspicious_only: bool,
+) -> (String, Vec<(Range<usize>, bool)>) {
     let mut output = String::new();
+    let mut glyphs = Vec::new();
 
-    let tab_width = if tab_width == 0 { 4 } else { tab_width };
+    let tab_width = if tab_width == 0 {
+        DEFAULT_TAB_WIDTH
+    } else {
+        tab_width
+    };
 
     for chr in input.chars() {
-        match chr {
+        let start = output.len();
+        let suspicious = match chr {
+            ' ' | '\t' | '\x0A' | '\x0D' if suspicious_only => {
+                output.push(chr);
+                continue;
+            }
             // space
-            ' ' => output.push('•'),
+            ' ' => {
+                output.push('•');
+                false
+            }
             // tab
             '\t' => {
                 if tab_width == 1 {
@@ -51,23 +88,139 @@ pub fn replace_nonprintable(input: &str, tab_width: usize) -> String {
                     output.push_str(&"─".repeat(tab_width - 2));
                     output.push('┤');
                 }
+                false
             }
             // line feed
-            '\x0A' => output.push('␊'),
+            '\x0A' => {
+                output.push('␊');
+                false
+            }
             // carriage return
-            '\x0D' => output.push('␍'),
-            // null
-            '\x00' => output.push('␀'),
-            // bell
-            '\x07' => output.push('␇'),
-            // backspace
-            '\x08' => output.push('␈'),
-            // escape
-            '\x1B' => output.push('␛'),
+            '\x0D' => {
+                output.push('␍');
+                false
+            }
+            // other C0 controls, like null, bell, backspace and escape
+            '\x00'..='\x1F' => {
+                output.extend(char::from_u32(0x2400 + chr as u32));
+                true
+            }
+            // delete
+            '\x7F' => {
+                output.push('␡');
+                true
+            }
+            // no-break space
+            '\u{A0}' => {
+                output.push('⍽');
+                true
+            }
+            // C1 controls, format characters (like zero-width spaces and bidi
+            // controls) and whitespace which looks like a space
+            _ if !chr.is_ascii() && INVISIBLE.is_match(chr.encode_utf8(&mut [0; 4])) => {
+                output.push_str(&format!("<U+{:04X}>", chr as u32));
+                true
+            }
             // anything else
-            _ => output.push(chr),
+            _ => {
+                output.push(chr);
+                continue;
+            }
+        };
+        glyphs.push((start..output.len(), suspicious));
+    }
+
+    (output, glyphs)
+}
+
+/// Maps byte offsets in a line to those in the output of
+/// `replace_nonprintable`, e.g. to find matches in the line and mark them in
+/// the output.
+#[derive(Debug, Default)]
+pub struct GlyphOffsets {
+    /// The end of every replaced character in the line, and how much longer
+    /// the output is up to there
+    shifts: Vec<(usize, usize)>,
+}
+
+impl GlyphOffsets {
+    /// Takes the glyphs returned by `replace_nonprintable` for `line`.
+    pub fn new(line: &str, glyphs: &[(Range<usize>, bool)]) -> Self {
+        let mut shifts = Vec::new();
+        let mut shift = 0;
+        let mut glyphs = glyphs.iter().peekable();
+        for (index, chr) in line.char_indices() {
+            // Glyphs are never shorter than the characters they replace.
+            if let Some((glyph, _)) = glyphs.next_if(|(glyph, _)| glyph.start == index + shift) {
+                shift += glyph.len() - chr.len_utf8();
+                shifts.push((index + chr.len_utf8(), shift));
+            }
+        }
+        GlyphOffsets { shifts }
+    }
+
+    /// The offset in the output of the character at `position` in the line.
+    pub fn offset(&self, position: usize) -> usize {
+        let replaced = self.shifts.partition_point(|&(end, _)| end <= position);
+        match replaced {
+            0 => position,
+            replaced => position + self.shifts[replaced - 1].1,
         }
     }
 
-    output
+    pub fn range(&self, range: Range<usize>) -> Range<usize> {
+        self.offset(range.start)..self.offset(range.end)
+    }
+}
+
+#[test]
+fn test_replace_nonprintable() {
+    let (output, glyphs) = replace_nonprintable("a b\t\x1b\u{202E}\n", 4, false);
+    assert_eq!("a•b├──┤␛<U+202E>␊", output);
+    let texts: Vec<_> = glyphs
+        .iter()
+        .map(|(range, suspicious)| (&output[range.clone()], *suspicious))
+        .collect();
+    assert_eq!(
+        vec![
+            ("•", false),
+            ("├──┤", false),
+            ("␛", true),
+            ("<U+202E>", true),
+            ("␊", false)
+        ],
+        texts
+    );
+}
+
+#[test]
+fn test_replace_suspicious_only() {
+    let line = "let\u{A0}x = \"\u{200B}\u{2066}ok\u{85}\";\r\n";
+    let (output, glyphs) = replace_nonprintable(line, 4, true);
+    assert_eq!("let⍽x = \"<U+200B><U+2066>ok<U+0085>\";\r\n", output);
+    assert_eq!(4, glyphs.len());
+    assert!(glyphs.iter().all(|&(_, suspicious)| suspicious));
+
+    let (output, glyphs) = replace_nonprintable("naïve café\t日本\n", 4, true);
+    assert_eq!("naïve café\t日本\n", output);
+    assert!(glyphs.is_empty());
+}
+
+#[test]
+fn test_glyph_offsets() {
+    let line = "a b\tc\u{200B}d\n";
+    let (output, glyphs) = replace_nonprintable(line, 4, false);
+    let offsets = GlyphOffsets::new(line, &glyphs);
+
+    let texts: Vec<_> = ["a", "b\tc", "d\n", ""]
+        .iter()
+        .map(|text| {
+            let start = line.rfind(text).unwrap();
+            &output[offsets.range(start..start + text.len())]
+        })
+        .collect();
+    assert_eq!(vec!["a", "b├──┤c", "d␊", ""], texts);
+
+    let offsets = GlyphOffsets::default();
+    assert_eq!(3..5, offsets.range(3..5));
 }
@@ -1,29 +1,49 @@
+use std::borrow::Cow;
 use std::io::Write;
+use std::ops::Range;
+use std::time::Instant;
 use std::vec::Vec;
 
-use ansi_term::Colour::Fixed;
+use ansi_term::Colour::{self, Fixed, Green, Red};
 use ansi_term::Style;
 
 use console::AnsiCodeIterator;
 
 use syntect::easy::HighlightLines;
-use syntect::highlighting::Theme;
-use syntect::parsing::SyntaxSet;
+use syntect::highlighting::{self, Color, FontStyle, HighlightIterator, Highlighter, Theme};
+use syntect::parsing::{Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
 
 use content_inspector::ContentType;
 
-use encoding::all::{UTF_16BE, UTF_16LE};
-use encoding::{DecoderTrap, Encoding};
+use regex::Regex;
+
+use encoding::EncodingRef;
 
 use crate::assets::HighlightingAssets;
-use crate::decorations::{Decoration, GridBorderDecoration, LineNumberDecoration};
+use crate::audit::{audit_line, Finding, FindingKind};
+use crate::brackets::BracketMatcher;
+use crate::charset;
+use crate::checkpoint::HighlightCheckpoint;
+use crate::decorations::{
+    AuditDecoration, Decoration, GridBorderDecoration, LineChangesDecoration, LineNumberDecoration,
+};
+use crate::diff::{DiffLine, LineChange};
+use crate::embed::{EmbedHighlighter, Embedding};
 use crate::errors::*;
+use crate::hexdump::{format_row, BinaryMode, OFFSET_WIDTH};
 use crate::inputfile::{InputFile, InputFileReader};
-use crate::preprocessor::{expand_tabs, replace_nonprintable};
+use crate::line_ending::{normalize_line_ending, split_line_ending, LineEnding, LineEndingMode};
+use crate::log::{is_log, LogHighlighter};
+use crate::patch::{is_patch, PatchHighlighter};
+use crate::preprocessor::{expand_tabs, replace_nonprintable, GlyphOffsets, DEFAULT_TAB_WIDTH};
+use crate::safeguard::{truncate_line, LineSafeguards, LongLines, PrintReport};
+use crate::scopes::{in_string_or_comment, scopes_at};
+use crate::search::find_matches;
 use crate::style::OutputComponents;
 use crate::style::OutputWrap;
 use crate::syntax_mapping::SyntaxMapping;
 use crate::terminal::{as_terminal_escaped, to_ansi_color};
+use crate::whitespace::{draw_guides, whitespace_errors, IndentGuides, IndentStyle};
 
 pub trait Printer {
     fn print_header(
@@ -33,6 +53,8 @@ pub trait Printer {
         header_overwrite: Option<String>,
     ) -> Result<()>;
     fn print_footer(&mut self, handle: &mut dyn Write) -> Result<()>;
+    /// Prints a horizontal separator with a label, e.g. to mark skipped lines.
+    fn print_separator(&mut self, handle: &mut dyn Write, label: &str) -> Result<()>;
     fn print_line(
         &mut self,
         out_of_range: bool,
@@ -40,6 +62,54 @@ pub trait Printer {
         line_number: usize,
         line_buffer: &[u8],
     ) -> Result<()>;
+    /// The current state of the syntax highlighter, if the input is highlighted.
+    fn highlight_state(&mut self) -> Option<HighlightCheckpoint>;
+    /// Continues highlighting from a state returned by `highlight_state`.
+    fn set_highlight_state(&mut self, state: HighlightCheckpoint);
+    /// The state of a fresh syntax highlighter after an empty line.
+    fn resync_state(&self) -> Option<HighlightCheckpoint>;
+    /// The number of bytes per row, if the input is printed as a hex dump.
+    fn bytes_per_row(&self) -> Option<usize>;
+    /// Prints a row of a hex dump, starting at the given byte offset.
+    fn print_row(&mut self, handle: &mut dyn Write, offset: usize, bytes: &[u8]) -> Result<()>;
+}
+
+/// How the printer shows the lines of a file.
+#[derive(Default)]
+pub struct PrinterOptions<'o> {
+    pub output_components: OutputComponents,
+    pub theme: String,
+    pub colored_output: bool,
+    pub true_color: bool,
+    pub term_width: usize,
+    /// The name of the syntax to use instead of the detected one
+    pub language: Option<String>,
+    pub syntax_mapping: SyntaxMapping,
+    pub tab_width: usize,
+    pub show_nonprintable: bool,
+    pub flag_nonprintable: bool,
+    pub audit: bool,
+    pub whitespace_lint: bool,
+    pub indent_guides: IndentGuides,
+    pub rainbow_brackets: bool,
+    pub output_wrap: OutputWrap,
+    pub use_italic_text: bool,
+    pub language_guessing: bool,
+    pub highlight_patches: bool,
+    pub highlight_logs: bool,
+    pub highlight_log_json: bool,
+    pub embeddings: &'o [Embedding],
+    /// Overrides the detected encoding of the input
+    pub encoding: Option<EncodingRef>,
+    pub detect_encoding: bool,
+    pub line_ending_mode: LineEndingMode,
+    pub eof_marker: bool,
+    pub binary_mode: BinaryMode,
+    /// The number of bytes in a row of a hex dump
+    pub bytes_per_row: usize,
+    /// Matches of the pattern are highlighted like search results
+    pub highlight_pattern: Option<Regex>,
+    pub safeguards: LineSafeguards,
 }
 
 pub struct InteractivePrinter<'a> {
@@ -48,7 +118,25 @@ pub struct InteractivePrinter<'a> {
     panel_width: usize,
     ansi_prefix_sgr: String,
     content_type: ContentType,
+    encoding: Option<EncodingRef>,
+    line_ending: Option<LineEnding>,
+    line_ending_mode: LineEndingMode,
+    eof_marker: bool,
     highlighter: Option<HighlightLines<'a>>,
+    theme: &'a Theme,
+    /// Styles the scopes found while parsing a line
+    theme_highlighter: Highlighter<'a>,
+    /// The style of suspicious or invalid content
+    error_style: highlighting::Style,
+    patch_highlighter: Option<PatchHighlighter<'a>>,
+    log_highlighter: Option<LogHighlighter<'a>>,
+    embed_highlighter: Option<EmbedHighlighter<'a>>,
+    bytes_per_row: Option<usize>,
+    highlight_pattern: Option<Regex>,
+    safeguards: LineSafeguards,
+    highlighting_disabled: bool,
+    report: PrintReport,
+    syntax: Option<&'a SyntaxReference>,
     syntax_set: &'a SyntaxSet,
     output_components: OutputComponents,
     colored_output: bool,
@@ -56,8 +144,22 @@ pub struct InteractivePrinter<'a> {
     term_width: usize,
     tab_width: usize,
     show_nonprintable: bool,
+    flag_nonprintable: bool,
+    audit: bool,
+    whitespace_lint: bool,
+    indent_style: Option<IndentStyle>,
+    indent_guides: IndentGuides,
+    indent_width: usize,
+    /// The width of the indentation of the current line in which guides are
+    /// drawn
+    guided_columns: usize,
+    rainbow_brackets: bool,
+    brackets: BracketMatcher,
     output_wrap: OutputWrap,
     use_italic_text: bool,
+    line_change: Option<LineChange>,
+    line_flagged: bool,
+    overlays: Vec<(Range<usize>, Colour)>,
 }
 
 impl<'a> InteractivePrinter<'a> {
@@ -65,18 +167,39 @@ impl<'a> InteractivePrinter<'a> {
         assets: &'a HighlightingAssets,
         file: &InputFile,
         reader: &mut InputFileReader,
-        output_components: OutputComponents,
-        theme: String,
-        colored_output: bool,
-        true_color: bool,
-        term_width: usize,
-        language: Option<String>,
-        syntax_mapping: SyntaxMapping,
-        tab_width: usize,
-        show_nonprintable: bool,
-        output_wrap: OutputWrap,
-        use_italic_text: bool,
+        options: PrinterOptions,
     ) -> Self {
+        let PrinterOptions {
+            output_components,
+            theme,
+            colored_output,
+            true_color,
+            term_width,
+            language,
+            syntax_mapping,
+            tab_width,
+            show_nonprintable,
+            flag_nonprintable,
+            audit,
+            whitespace_lint,
+            indent_guides,
+            rainbow_brackets,
+            output_wrap,
+            use_italic_text,
+            language_guessing,
+            highlight_patches,
+            highlight_logs,
+            highlight_log_json,
+            embeddings,
+            encoding,
+            detect_encoding,
+            line_ending_mode,
+            eof_marker,
+            binary_mode,
+            bytes_per_row,
+            highlight_pattern,
+            safeguards,
+        } = options;
         let theme = assets.get_theme(&theme);
 
         let colors = if colored_output {
@@ -92,6 +215,16 @@ impl<'a> InteractivePrinter<'a> {
             decorations.push(Box::new(LineNumberDecoration::new(&colors)));
         }
 
+        if output_components.changes() {
+            decorations.push(Box::new(LineChangesDecoration::new(&colors)));
+        }
+
+        // The audit marker gets a panel of its own when no other
+        // decorations are shown.
+        if audit {
+            decorations.push(Box::new(AuditDecoration::new(&colors)));
+        }
+
         let mut panel_width: usize =
             decorations.len() + decorations.iter().fold(0, |a, x| a + x.width());
 
@@ -109,21 +242,117 @@ impl<'a> InteractivePrinter<'a> {
             panel_width = 0;
         }
 
-        let highlighter = if reader.content_type.is_binary() {
+        // An explicit encoding overrides the detected content type, e.g. for
+        // UTF-16 inputs without a byte order mark.
+        if let Some(encoding) = encoding {
+            reader.content_type = charset::content_type(encoding);
+            reader.set_encoding(Some(encoding));
+        } else if detect_encoding && reader.content_type == ContentType::UTF_8 {
+            if let Some(detected) = charset::detect(&reader.sample()) {
+                reader.set_encoding(Some(detected));
+            }
+        }
+        let encoding = reader.encoding;
+        let line_ending = reader.line_ending();
+        let indent_style = reader.indent_style();
+
+        // The whitespace lint and the guides draw over tabs, so they are
+        // expanded.
+        let tab_width = match tab_width {
+            0 if whitespace_lint || indent_guides != IndentGuides::Off => DEFAULT_TAB_WIDTH,
+            tab_width => tab_width,
+        };
+        let indent_width = match indent_style {
+            Some(IndentStyle::Spaces) => reader.indent_width().unwrap_or(tab_width),
+            _ => tab_width,
+        };
+
+        // Hex dumps show the offset of every row instead of the decorations.
+        let bytes_per_row = Some(bytes_per_row.max(1))
+            .filter(|_| reader.content_type.is_binary() && binary_mode == BinaryMode::HexDump);
+        if bytes_per_row.is_some() {
+            panel_width = if output_components.numbers() {
+                OFFSET_WIDTH + 1
+            } else {
+                0
+            };
+        }
+
+        let syntax = if reader.content_type.is_binary() {
             None
         } else {
             // Determine the type of syntax for highlighting
-            let syntax = assets.get_syntax(language, file, reader, &syntax_mapping);
-            Some(HighlightLines::new(syntax, theme))
+            Some(assets.get_syntax(
+                language.clone(),
+                file,
+                reader,
+                &syntax_mapping,
+                language_guessing,
+            ))
+        };
+
+        // Patches are highlighted as diffs, with the content of their hunks
+        // highlighted in the language of the patched files.
+        let is_patch = highlight_patches
+            && syntax.is_some_and(|syntax| is_patch(&syntax.name, &reader.first_line));
+        let syntax = match assets.syntax_set.find_syntax_by_name("Diff") {
+            Some(diff) if is_patch => Some(diff),
+            _ => syntax,
+        };
+        let patch_highlighter = if is_patch {
+            Some(PatchHighlighter::new(assets, theme, syntax_mapping))
+        } else {
+            None
         };
 
+        // Logs are highlighted by their tokens instead of a syntax.
+        let is_log = highlight_logs
+            && syntax.is_some_and(|syntax| {
+                is_log(language.as_deref(), &syntax.name, file, &reader.first_line)
+            });
+        let log_highlighter = if is_log {
+            Some(LogHighlighter::new(
+                theme,
+                &assets.syntax_set,
+                highlight_log_json,
+            ))
+        } else {
+            None
+        };
+
+        // Blocks of other languages, like code fences, are highlighted in
+        // their own syntax.
+        let embed_highlighter = syntax.filter(|_| !is_patch && !is_log).and_then(|syntax| {
+            EmbedHighlighter::new(embeddings, syntax, theme, &assets.syntax_set)
+        });
+
+        let highlighter = syntax.map(|syntax| HighlightLines::new(syntax, theme));
+        let theme_highlighter = Highlighter::new(theme);
+        let error_style = error_style(theme, &theme_highlighter);
+
         InteractivePrinter {
             panel_width,
             colors,
             decorations,
             content_type: reader.content_type,
+            encoding,
+            line_ending,
+            line_ending_mode,
+            eof_marker,
             ansi_prefix_sgr: String::new(),
             highlighter,
+            theme,
+            theme_highlighter,
+            error_style,
+            patch_highlighter,
+            log_highlighter,
+            embed_highlighter,
+            bytes_per_row,
+            highlight_pattern,
+            safeguards,
+            highlighting_disabled: false,
+            report: PrintReport::default(),
+            syntax,
             syntax_set: &assets.syntax_set,
             output_components,
             colored_output,
@@ -131,11 +360,161 @@ impl<'a> InteractivePrinter<'a> {
             term_width,
             tab_width,
             show_nonprintable,
+            flag_nonprintable,
+            audit,
+            whitespace_lint,
+            indent_style,
+            indent_guides,
+            indent_width,
+            guided_columns: 0,
+            rainbow_brackets,
+            brackets: BracketMatcher::default(),
             output_wrap,
             use_italic_text,
+            line_change: None,
+            line_flagged: false,
+            overlays: vec![],
+        }
+    }
+
+    /// The change marker of the line that is currently printed.
+    pub fn line_change(&self) -> Option<LineChange> {
+        self.line_change
+    }
+
+    /// Whether the audit found suspicious content in the line that is
+    /// currently printed.
+    pub fn line_flagged(&self) -> bool {
+        self.line_flagged
+    }
+
+    /// Prints one line of a diff, with a change marker and the changed words
+    /// emphasized.
+    pub fn print_diff_line(
+        &mut self,
+        handle: &mut dyn Write,
+        out_of_range: bool,
+        line: &DiffLine,
+        change: Option<LineChange>,
+    ) -> Result<()> {
+        let background = match change {
+            Some(LineChange::Added) => self.colors.diff_added_emphasis,
+            Some(LineChange::Removed) => self.colors.diff_removed_emphasis,
+            None => None,
+        };
+
+        self.line_change = change;
+        self.overlays = match background {
+            Some(background) => line
+                .emphasis
+                .iter()
+                .map(|range| (range.clone(), background))
+                .collect(),
+            None => vec![],
+        };
+
+        let result = self.print_line(out_of_range, handle, line.number, line.text.as_bytes());
+
+        self.line_change = None;
+        self.overlays.clear();
+        result
+    }
+
+    /// Prints two rendered columns next to each other, separated by a grid
+    /// line. `left` is padded to `column_width`.
+    pub fn print_side_by_side(
+        &mut self,
+        handle: &mut dyn Write,
+        left: &[u8],
+        right: &[u8],
+        column_width: usize,
+    ) -> Result<()> {
+        let left = String::from_utf8_lossy(left);
+        let right = String::from_utf8_lossy(right);
+        let mut left_lines = left.lines();
+        let mut right_lines = right.lines();
+
+        loop {
+            let (l, r) = match (left_lines.next(), right_lines.next()) {
+                (None, None) => break,
+                (l, r) => (l.unwrap_or(""), r.unwrap_or("")),
+            };
+
+            let padding = column_width.saturating_sub(console::measure_text_width(l));
+            writeln!(
+                handle,
+                "{}{}{}{}",
+                l,
+                " ".repeat(padding),
+                self.colors.grid.paint("│"),
+                r
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns which safeguards took effect, and resets them.
+    pub fn take_report(&mut self) -> PrintReport {
+        std::mem::take(&mut self.report)
+    }
+
+    /// The style of lines which are not highlighted.
+    fn plain_style(&self) -> highlighting::Style {
+        plain_style(self.theme)
+    }
+
+    /// The style of the glyphs of invisible characters.
+    fn nonprintable_style(&self, suspicious: bool) -> highlighting::Style {
+        if suspicious {
+            return self.error_style;
+        }
+
+        highlighting::Style {
+            foreground: self
+                .theme
+                .settings
+                .gutter_foreground
+                .unwrap_or(NONPRINTABLE_COLOR),
+            ..self.plain_style()
         }
     }
 
+    /// The style of a bracket at the given depth, or of an unbalanced one.
+    fn bracket_style(&self, depth: Option<usize>) -> highlighting::Style {
+        match depth {
+            Some(depth) => highlighting::Style {
+                foreground: BRACKET_COLORS[depth % BRACKET_COLORS.len()],
+                ..self.plain_style()
+            },
+            None => self.error_style,
+        }
+    }
+
+    /// The style of indentation guides.
+    fn guide_style(&self) -> highlighting::Style {
+        highlighting::Style {
+            foreground: self
+                .theme
+                .settings
+                .guide
+                .or(self.theme.settings.gutter_foreground)
+                .unwrap_or(NONPRINTABLE_COLOR),
+            ..self.plain_style()
+        }
+    }
+
+    /// The syntax used for highlighting, if the input is text.
+    pub fn syntax(&self) -> Option<&'a SyntaxReference> {
+        self.syntax
+    }
+
+    /// Whether the content of the input is printed, i.e. if it's text or a
+    /// hex dump.
+    fn shows_content(&self) -> bool {
+        self.content_type.is_text() || self.bytes_per_row.is_some()
+    }
+
     fn print_horizontal_line(&mut self, handle: &mut dyn Write, grid_char: char) -> Result<()> {
         if self.panel_width == 0 {
             writeln!(
@@ -153,12 +532,48 @@ impl<'a> InteractivePrinter<'a> {
     }
 
     fn preprocess(&self, text: &str, cursor: &mut usize) -> String {
-        if self.tab_width > 0 {
+        let start = *cursor;
+        let text = if self.tab_width > 0 {
             expand_tabs(text, self.tab_width, cursor)
         } else {
             text.to_string()
+        };
+
+        match self.indent_guides.glyph() {
+            Some(glyph) if start < self.guided_columns => {
+                draw_guides(&text, start, self.guided_columns, self.indent_width, glyph)
+            }
+            _ => text,
         }
     }
+
+    /// Highlights `line` like the highlighter does, but also returns the
+    /// scopes at its beginning and the operations of the parser for it, which
+    /// tell the scopes of each character.
+    #[allow(clippy::type_complexity)]
+    fn highlight_scoped<'l>(
+        &mut self,
+        line: &'l str,
+    ) -> Option<(
+        Vec<(highlighting::Style, &'l str)>,
+        ScopeStack,
+        Vec<(usize, ScopeStackOp)>,
+    )> {
+        let (mut highlight_state, mut parse_state) = self.highlighter.take()?.state();
+
+        let start = highlight_state.path.clone();
+        let ops = parse_state.parse_line(line, self.syntax_set);
+        let regions = ops.as_ref().ok().map(|ops| {
+            HighlightIterator::new(&mut highlight_state, ops, line, &self.theme_highlighter)
+                .collect()
+        });
+        self.highlighter = Some(HighlightLines::from_state(
+            self.theme,
+            highlight_state,
+            parse_state,
+        ));
+        Some((regions?, start, ops.ok()?))
+    }
 }
 
 impl<'a> Printer for InteractivePrinter<'a> {
@@ -197,12 +612,19 @@ impl<'a> Printer for InteractivePrinter<'a> {
             },
         };
 
-        let mode = match self.content_type {
-            ContentType::BINARY => "   <BINARY>",
-            ContentType::UTF_16LE => "   <UTF-16LE>",
-            ContentType::UTF_16BE => "   <UTF-16BE>",
-            _ => "",
+        let mut mode = match (self.content_type, self.encoding) {
+            (ContentType::BINARY, _) => "   <BINARY>".to_string(),
+            (_, Some(encoding)) if encoding.name() != "utf-8" => {
+                format!("   <{}>", charset::display_name(encoding))
+            }
+            _ => String::new(),
         };
+        match self.line_ending {
+            Some(line_ending) if line_ending != LineEnding::Lf && self.content_type.is_text() => {
+                mode.push_str(&format!("   <{}>", line_ending.name()));
+            }
+            _ => {}
+        }
 
         writeln!(
             handle,
@@ -213,7 +635,7 @@ impl<'a> Printer for InteractivePrinter<'a> {
         )?;
 
         if self.output_components.grid() {
-            if self.content_type.is_text() {
+            if self.shows_content() {
                 self.print_horizontal_line(handle, '┼')?;
             } else {
                 self.print_horizontal_line(handle, '┴')?;
@@ -223,14 +645,114 @@ impl<'a> Printer for InteractivePrinter<'a> {
         Ok(())
     }
 
+    fn print_separator(&mut self, handle: &mut dyn Write, label: &str) -> Result<()> {
+        let label = format!(" {} ", label);
+        let label_width = label.chars().count();
+
+        if !self.output_components.grid() {
+            writeln!(
+                handle,
+                "{}{}",
+                " ".repeat(self.panel_width),
+                self.colors.grid.paint(label.trim_start())
+            )?;
+            return Ok(());
+        }
+
+        let prefix = if self.panel_width > 0 {
+            format!("{}┼─", "─".repeat(self.panel_width))
+        } else {
+            "─".to_string()
+        };
+        let used = prefix.chars().count() + label_width;
+        let line = format!(
+            "{}{}{}",
+            prefix,
+            label,
+            "─".repeat(self.term_width.saturating_sub(used))
+        );
+        writeln!(handle, "{}", self.colors.grid.paint(line))?;
+
+        Ok(())
+    }
+
     fn print_footer(&mut self, handle: &mut dyn Write) -> Result<()> {
-        if self.output_components.grid() && self.content_type.is_text() {
+        if self.output_components.grid() && self.shows_content() {
             self.print_horizontal_line(handle, '┴')
         } else {
             Ok(())
         }
     }
 
+    fn highlight_state(&mut self) -> Option<HighlightCheckpoint> {
+        // The state of the embedded highlighting of patches and blocks, and
+        // the depth of brackets aren't captured.
+        if self.patch_highlighter.is_some()
+            || self.rainbow_brackets
+            || self
+                .embed_highlighter
+                .as_ref()
+                .is_some_and(|embed| embed.in_block())
+        {
+            return None;
+        }
+
+        let (highlight_state, parse_state) = self.highlighter.take()?.state();
+        let state = (highlight_state.clone(), parse_state.clone());
+        self.highlighter = Some(HighlightLines::from_state(
+            self.theme,
+            highlight_state,
+            parse_state,
+        ));
+        Some(state)
+    }
+
+    fn set_highlight_state(&mut self, state: HighlightCheckpoint) {
+        if self.highlighter.is_some() {
+            self.highlighter = Some(HighlightLines::from_state(self.theme, state.0, state.1));
+        }
+    }
+
+    fn resync_state(&self) -> Option<HighlightCheckpoint> {
+        let mut highlighter = HighlightLines::new(self.syntax?, self.theme);
+        highlighter.highlight_line("\n", self.syntax_set).ok()?;
+        Some(highlighter.state())
+    }
+
+    fn bytes_per_row(&self) -> Option<usize> {
+        self.bytes_per_row
+    }
+
+    fn print_row(&mut self, handle: &mut dyn Write, offset: usize, bytes: &[u8]) -> Result<()> {
+        let bytes_per_row = self.bytes_per_row.unwrap_or(bytes.len());
+        let (hex, text) = format_row(bytes, bytes_per_row, self.colored_output);
+        let separator = self.colors.grid.paint(if self.output_components.grid() {
+            "│ "
+        } else {
+            "  "
+        });
+
+        if self.panel_width > 0 {
+            write!(
+                handle,
+                "{}{}",
+                self.colors.line_number.paint(format!(
+                    "{:0width$x} ",
+                    offset,
+                    width = OFFSET_WIDTH
+                )),
+                self.colors.grid.paint(if self.output_components.grid() {
+                    "│ "
+                } else {
+                    ""
+                })
+            )?;
+        }
+        writeln!(handle, "{} {}{}", hex, separator, text)?;
+
+        Ok(())
+    }
+
     fn print_line(
         &mut self,
         out_of_range: bool,
@@ -238,37 +760,241 @@ impl<'a> Printer for InteractivePrinter<'a> {
         line_number: usize,
         line_buffer: &[u8],
     ) -> Result<()> {
-        let mut line = match self.content_type {
-            ContentType::BINARY => {
-                return Ok(());
+        if self.content_type.is_binary() {
+            return Ok(());
+        }
+        let mut line = String::from_utf8_lossy(line_buffer).into_owned();
+        // Only the last line of an input can lack a line ending.
+        let missing_newline = split_line_ending(&line).1.is_empty();
+        normalize_line_ending(&mut line, self.line_ending_mode);
+
+        // Very long lines are not highlighted, since that may take forever.
+        // They are measured in bytes of the input, before invisible
+        // characters are replaced by glyphs.
+        let too_long = self
+            .safeguards
+            .max_line_length
+            .is_some_and(|max| split_line_ending(&line).0.len() > max);
+        if too_long {
+            if let (LongLines::Truncate, Some(max)) =
+                (self.safeguards.long_lines, self.safeguards.max_line_length)
+            {
+                line = truncate_line(&line, max);
             }
-            ContentType::UTF_16LE => UTF_16LE
-                .decode(&line_buffer, DecoderTrap::Strict)
-                .unwrap_or("Invalid UTF-16LE".into()),
-            ContentType::UTF_16BE => UTF_16BE
-                .decode(&line_buffer, DecoderTrap::Strict)
-                .unwrap_or("Invalid UTF-16BE".into()),
-            _ => String::from_utf8_lossy(&line_buffer).to_string(),
-        };
+        }
+
+        // Bidirectional controls would reorder the output, so the audit
+        // always replaces them. Ranges found in the input are mapped to the
+        // glyphs which replace it.
+        let input = line;
+        let (line, glyphs, offsets) =
+            if self.show_nonprintable || self.flag_nonprintable || self.audit {
+                let suspicious_only = !self.show_nonprintable;
+                let (replaced, glyphs) =
+                    replace_nonprintable(&input, self.tab_width, suspicious_only);
+                let offsets = GlyphOffsets::new(&input, &glyphs);
+                (Cow::Owned(replaced), glyphs, offsets)
+            } else {
+                (Cow::Borrowed(&input[..]), vec![], GlyphOffsets::default())
+            };
+
+        let plain = self.highlighting_disabled
+            || (too_long && self.safeguards.long_lines == LongLines::Plain);
+        let plain_style = self.plain_style();
 
-        if self.show_nonprintable {
-            line = replace_nonprintable(&mut line, self.tab_width);
+        // Guides are drawn over the indentation of lines with content, unless
+        // it belongs to a string or comment which spans several lines, like a
+        // block scalar in YAML.
+        let indentation = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let blank = split_line_ending(&line[indentation..]).0.is_empty();
+        let guided = self.indent_guides != IndentGuides::Off && indentation > 0 && !blank && !plain;
+        if self.highlighter.is_none() {
+            return Ok(());
         }
 
-        let regions = {
-            let highlighter = match self.highlighter {
-                Some(ref mut highlighter) => highlighter,
-                _ => {
-                    return Ok(());
+        let (regions, scopes) = if plain {
+            if let Some(ref mut embed_highlighter) = self.embed_highlighter {
+                embed_highlighter.skip(&line);
+            }
+            (vec![(plain_style, line.as_ref())], None)
+        } else {
+            let start = Instant::now();
+            let (regions, scopes) = match self.log_highlighter {
+                Some(ref log_highlighter) => {
+                    (log_highlighter.highlight(&line, self.syntax_set), None)
+                }
+                // Guides and brackets need the scopes of the characters.
+                None if guided || self.rainbow_brackets => match self.highlight_scoped(&line) {
+                    Some((regions, start, ops)) => (regions, Some((start, ops))),
+                    None => (vec![(plain_style, line.as_ref())], None),
+                },
+                None => {
+                    let highlighter = self.highlighter.as_mut().unwrap();
+                    (highlighter.highlight(line.as_ref(), self.syntax_set), None)
                 }
             };
-            highlighter.highlight(line.as_ref(), self.syntax_set)
+            // The outer syntax still sees embedded blocks, so that its state
+            // continues correctly after them.
+            let regions = match self.embed_highlighter {
+                Some(ref mut embed_highlighter) => {
+                    embed_highlighter.highlight(&line).unwrap_or(regions)
+                }
+                None => regions,
+            };
+
+            if self
+                .safeguards
+                .highlight_timeout
+                .is_some_and(|timeout| start.elapsed() > timeout)
+            {
+                self.highlighting_disabled = true;
+                self.report.slow_line = Some(line_number);
+            }
+            (regions, scopes)
+        };
+
+        self.guided_columns = 0;
+        if guided
+            && !scopes.as_ref().is_some_and(|(start, ops)| {
+                in_string_or_comment(&scopes_at(start, ops, indentation - 1))
+            })
+        {
+            expand_tabs(
+                &line[..indentation],
+                self.tab_width,
+                &mut self.guided_columns,
+            );
+        }
+
+        // The emphasis of changed words is found in the input.
+        let mut overlays: Vec<_> = self
+            .overlays
+            .iter()
+            .filter(|(range, _)| range.end <= input.len())
+            .map(|(range, background)| (offsets.range(range.clone()), *background))
+            .collect();
+
+        // Replace the content of hunks with the regions of the embedded
+        // language, keeping the marker and adding a background.
+        let patch_content = match self.patch_highlighter {
+            Some(ref mut patch_highlighter) if !plain => {
+                patch_highlighter.highlight(&line, self.syntax_set)
+            }
+            _ => None,
+        };
+        let regions = match patch_content {
+            Some((change, content)) => {
+                let content_len: usize = content.iter().map(|(_, text)| text.len()).sum();
+                let start = line.len() - content_len;
+                let end = line.trim_end_matches(['\r', '\n']).len();
+
+                let background = match change {
+                    Some(LineChange::Added) => self.colors.diff_added_background,
+                    Some(LineChange::Removed) => self.colors.diff_removed_background,
+                    None => None,
+                };
+                if let Some(background) = background.filter(|_| start < end) {
+                    overlays.insert(0, (start..end, background));
+                }
+
+                let marker = regions
+                    .first()
+                    .filter(|_| start > 0)
+                    .map(|&(style, _)| (style, &line[..start]));
+                marker.into_iter().chain(content).collect()
+            }
+            None => regions,
+        };
+
+        // Brackets are matched on all lines, so that their depth is right on
+        // the printed ones.
+        let brackets = match scopes.filter(|_| self.rainbow_brackets) {
+            Some((start, ops)) => self.brackets.match_line(&line, &start, &ops),
+            None => vec![],
         };
 
         if out_of_range {
             return Ok(());
         }
 
+        // The audit looks at the line as it is, so that the columns of the
+        // findings are those of the input.
+        let audited = if self.audit {
+            audit_line(&input)
+        } else {
+            vec![]
+        };
+        let findings: Vec<_> = audited
+            .iter()
+            .map(|(range, kind)| Finding {
+                line: line_number,
+                column: input[..range.start].chars().count() + 1,
+                character: input[range.clone()].chars().next().unwrap_or_default(),
+                kind: *kind,
+            })
+            .collect();
+
+        // Glyphs of invisible characters are styled separately from the code.
+        // Letters found by the audit are marked like suspicious glyphs, while
+        // bidirectional controls are glyphs already.
+        let mut glyph_styles: Vec<_> = glyphs
+            .into_iter()
+            .filter(|(range, _)| range.end <= line.len())
+            .map(|(range, suspicious)| (range, self.nonprintable_style(suspicious)))
+            .collect();
+        let suspicious = self.nonprintable_style(true);
+        glyph_styles.extend(
+            audited
+                .into_iter()
+                .filter(|(_, kind)| *kind != FindingKind::BidiControl)
+                .map(|(range, _)| (offsets.range(range), suspicious)),
+        );
+        if self.guided_columns > 0 {
+            glyph_styles.push((0..indentation, self.guide_style()));
+        }
+        glyph_styles.extend(
+            brackets
+                .into_iter()
+                .filter(|(range, _)| range.end <= line.len())
+                .map(|(range, depth)| (range, self.bracket_style(depth))),
+        );
+        glyph_styles.sort_by_key(|(range, _)| range.start);
+        let regions = restyle(regions, &glyph_styles);
+
+        if let Some(background) = self
+            .colors
+            .whitespace_error
+            .filter(|_| self.whitespace_lint)
+        {
+            overlays.extend(
+                whitespace_errors(&input, self.indent_style)
+                    .into_iter()
+                    .map(|range| offsets.range(range))
+                    .filter(|range| range.end <= line.len())
+                    .map(|range| (range, background)),
+            );
+        }
+
+        // Search matches are added last, so they take precedence. They are
+        // found in the input, like the lines matching the pattern.
+        if let (Some(pattern), Some(background)) =
+            (&self.highlight_pattern, self.colors.search_match)
+        {
+            let matches = find_matches(pattern, &input);
+            overlays.extend(
+                matches
+                    .into_iter()
+                    .map(|range| (offsets.range(range), background)),
+            );
+        }
+        let regions = apply_overlays(&regions, &overlays);
+
+        if too_long {
+            self.report.long_lines.push(line_number);
+        }
+        self.line_flagged = !findings.is_empty();
+        self.report.findings.extend(findings);
+
         let mut cursor: usize = 0;
         let mut cursor_max: usize = self.term_width;
         let mut cursor_total: usize = 0;
@@ -288,26 +1014,40 @@ impl<'a> Printer for InteractivePrinter<'a> {
             }
         }
 
+        // The line ending was normalized before, and is written after the
+        // content. The last line of an input may lack one.
+        let line_ending = match split_line_ending(&line).1 {
+            "" => "\n",
+            line_ending => line_ending,
+        };
+
         // Line contents.
         if self.output_wrap == OutputWrap::None {
             let true_color = self.true_color;
             let colored_output = self.colored_output;
             let italics = self.use_italic_text;
 
-            for &(style, region) in regions.iter() {
+            for &(style, region, background) in regions.iter() {
+                let region = region.trim_end_matches(['\r', '\n']);
+                if region.is_empty() {
+                    continue;
+                }
                 let text = &*self.preprocess(region, &mut cursor_total);
                 write!(
                     handle,
                     "{}",
-                    as_terminal_escaped(style, &*text, true_color, colored_output, italics,)
+                    as_terminal_escaped(
+                        style,
+                        &*text,
+                        true_color,
+                        colored_output,
+                        italics,
+                        background
+                    )
                 )?;
             }
-
-            if line.bytes().next_back() != Some(b'\n') {
-                write!(handle, "\n")?;
-            }
         } else {
-            for &(style, region) in regions.iter() {
+            for &(style, region, background) in regions.iter() {
                 let ansi_iterator = AnsiCodeIterator::new(region);
                 let mut ansi_prefix: String = String::new();
                 for chunk in ansi_iterator {
@@ -355,7 +1095,8 @@ impl<'a> Printer for InteractivePrinter<'a> {
                                             ),
                                             self.true_color,
                                             self.colored_output,
-                                            self.use_italic_text
+                                            self.use_italic_text,
+                                            background
                                         )
                                     )?;
                                     break;
@@ -395,7 +1136,8 @@ impl<'a> Printer for InteractivePrinter<'a> {
                                         ),
                                         self.true_color,
                                         self.colored_output,
-                                        self.use_italic_text
+                                        self.use_italic_text,
+                                        background
                                     ),
                                     panel_wrap.clone().unwrap()
                                 )?;
@@ -407,21 +1149,179 @@ impl<'a> Printer for InteractivePrinter<'a> {
                     }
                 }
             }
+        }
+        write!(handle, "{}", line_ending)?;
 
-            write!(handle, "\n")?;
+        if missing_newline && self.eof_marker {
+            self.print_separator(handle, "no newline at end of file")?;
         }
 
         Ok(())
     }
 }
 
+/// Splits highlighted regions at the boundaries of the overlays, which are
+/// byte ranges of the line with a background color. Later overlays take
+/// precedence over earlier ones.
+fn apply_overlays<'b>(
+    regions: &[(highlighting::Style, &'b str)],
+    overlays: &[(Range<usize>, Colour)],
+) -> Vec<(highlighting::Style, &'b str, Option<Colour>)> {
+    let mut result = Vec::with_capacity(regions.len());
+    let mut offset = 0;
+
+    for &(style, text) in regions {
+        let end = offset + text.len();
+
+        let mut cuts: Vec<usize> = overlays
+            .iter()
+            .flat_map(|(range, _)| vec![range.start, range.end])
+            .filter(|&cut| cut > offset && cut < end && text.is_char_boundary(cut - offset))
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.push(end);
+
+        let mut position = offset;
+        for cut in cuts {
+            let background = overlays
+                .iter()
+                .rev()
+                .find(|(range, _)| range.contains(&position))
+                .map(|(_, background)| *background);
+            result.push((style, &text[position - offset..cut - offset], background));
+            position = cut;
+        }
+
+        offset = end;
+    }
+
+    result
+}
+
+/// Gives the parts of highlighted regions within the ranges the style of the
+/// range. The ranges must be sorted and must not overlap.
+fn restyle<'b>(
+    regions: Vec<(highlighting::Style, &'b str)>,
+    ranges: &[(Range<usize>, highlighting::Style)],
+) -> Vec<(highlighting::Style, &'b str)> {
+    if ranges.is_empty() {
+        return regions;
+    }
+
+    let mut result = Vec::with_capacity(regions.len() + 2 * ranges.len());
+    let mut offset = 0;
+    for (style, text) in regions {
+        let end = offset + text.len();
+        let mut position = offset;
+
+        for (range, range_style) in ranges
+            .iter()
+            .filter(|(range, _)| range.start < end && range.end > offset)
+        {
+            let (start, stop) = (range.start.max(position), range.end.min(end));
+            if !text.is_char_boundary(start - offset) || !text.is_char_boundary(stop - offset) {
+                continue;
+            }
+            if position < start {
+                result.push((style, &text[position - offset..start - offset]));
+            }
+            result.push((*range_style, &text[start - offset..stop - offset]));
+            position = stop;
+        }
+        if position < end {
+            result.push((style, &text[position - offset..]));
+        }
+
+        offset = end;
+    }
+
+    result
+}
+
+/// The style of lines which are not highlighted.
+fn plain_style(theme: &Theme) -> highlighting::Style {
+    highlighting::Style {
+        foreground: theme.settings.foreground.unwrap_or(Color::WHITE),
+        background: theme.settings.background.unwrap_or(Color::BLACK),
+        font_style: FontStyle::empty(),
+    }
+}
+
+/// The style of suspicious or invalid content. It has the color of invalid
+/// code, which usually is its background, or red if the theme has no style
+/// for it.
+fn error_style(theme: &Theme, highlighter: &Highlighter) -> highlighting::Style {
+    let plain = plain_style(theme);
+    let invalid = highlighter.style_for_stack(&[Scope::new("invalid.illegal").unwrap()]);
+    highlighting::Style {
+        foreground: if invalid.background != plain.background {
+            invalid.background
+        } else if invalid.foreground != plain.foreground {
+            invalid.foreground
+        } else {
+            SUSPICIOUS_COLOR
+        },
+        background: plain.background,
+        font_style: FontStyle::BOLD,
+    }
+}
+
+const NONPRINTABLE_COLOR: Color = Color {
+    r: 0x6c,
+    g: 0x6c,
+    b: 0x6c,
+    a: 0xff,
+};
+const SUSPICIOUS_COLOR: Color = Color {
+    r: 0xff,
+    g: 0x55,
+    b: 0x55,
+    a: 0xff,
+};
+/// The colors of brackets, by their depth of nesting
+const BRACKET_COLORS: [Color; 3] = [
+    Color {
+        r: 0xff,
+        g: 0xd7,
+        b: 0x00,
+        a: 0xff,
+    },
+    Color {
+        r: 0xda,
+        g: 0x70,
+        b: 0xd6,
+        a: 0xff,
+    },
+    Color {
+        r: 0x17,
+        g: 0x9f,
+        b: 0xff,
+        a: 0xff,
+    },
+];
 const DEFAULT_GUTTER_COLOR: u8 = 238;
+const DEFAULT_SEARCH_MATCH_COLOR: u8 = 94;
+const DIFF_ADDED_BACKGROUND_COLOR: u8 = 22;
+const DIFF_REMOVED_BACKGROUND_COLOR: u8 = 52;
+const DIFF_ADDED_EMPHASIS_COLOR: u8 = 28;
+const DIFF_REMOVED_EMPHASIS_COLOR: u8 = 88;
+const WHITESPACE_ERROR_COLOR: u8 = 124;
 
 #[derive(Default)]
 pub struct Colors {
     pub grid: Style,
     pub filename: Style,
     pub line_number: Style,
+    pub git_added: Style,
+    pub git_removed: Style,
+    pub diff_added_background: Option<Colour>,
+    pub diff_removed_background: Option<Colour>,
+    pub diff_added_emphasis: Option<Colour>,
+    pub diff_removed_emphasis: Option<Colour>,
+    pub search_match: Option<Colour>,
+    pub audit_marker: Style,
+    pub whitespace_error: Option<Colour>,
 }
 
 impl Colors {
@@ -436,10 +1336,217 @@ impl Colors {
             .map(|c| to_ansi_color(c, true_color))
             .unwrap_or(Fixed(DEFAULT_GUTTER_COLOR));
 
+        let search_match_color = theme
+            .settings
+            .find_highlight
+            .map(|c| to_ansi_color(c, true_color))
+            .unwrap_or(Fixed(DEFAULT_SEARCH_MATCH_COLOR));
+
         Colors {
             grid: gutter_color.normal(),
             filename: Style::new().bold(),
             line_number: gutter_color.normal(),
+            git_added: Green.normal(),
+            git_removed: Red.normal(),
+            diff_added_background: Some(Fixed(DIFF_ADDED_BACKGROUND_COLOR)),
+            diff_removed_background: Some(Fixed(DIFF_REMOVED_BACKGROUND_COLOR)),
+            diff_added_emphasis: Some(Fixed(DIFF_ADDED_EMPHASIS_COLOR)),
+            diff_removed_emphasis: Some(Fixed(DIFF_REMOVED_EMPHASIS_COLOR)),
+            search_match: Some(search_match_color),
+            audit_marker: Red.bold(),
+            whitespace_error: Some(Fixed(WHITESPACE_ERROR_COLOR)),
         }
     }
 }
+
+#[cfg(test)]
+fn overlay_texts(text: &str, overlays: &[(Range<usize>, Colour)]) -> Vec<(String, bool)> {
+    let style = highlighting::Style::default();
+    let regions: Vec<_> = text.split_inclusive(' ').map(|t| (style, t)).collect();
+    apply_overlays(&regions, overlays)
+        .into_iter()
+        .map(|(_, text, background)| (text.to_string(), background.is_some()))
+        .collect()
+}
+
+#[test]
+fn test_apply_overlays() {
+    let texts = overlay_texts("let x = 1;", &[]);
+    assert_eq!(4, texts.len());
+    assert!(texts.iter().all(|(_, emphasized)| !emphasized));
+
+    // Across region boundaries
+    let texts = overlay_texts("let x = 1;", &[(2..5, Red)]);
+    assert_eq!(
+        vec![
+            ("le".to_string(), false),
+            ("t ".to_string(), true),
+            ("x".to_string(), true),
+            (" ".to_string(), false),
+            ("= ".to_string(), false),
+            ("1;".to_string(), false),
+        ],
+        texts
+    );
+
+    // Never splits characters
+    let texts = overlay_texts("äö", &[(1..3, Red)]);
+    assert_eq!(vec![("äö".to_string(), false)], texts);
+}
+
+#[test]
+fn test_restyle() {
+    let plain = highlighting::Style::default();
+    let marked = highlighting::Style {
+        font_style: FontStyle::BOLD,
+        ..plain
+    };
+    let regions = vec![(plain, "let•x"), (plain, "•=•1;")];
+
+    let texts: Vec<_> = restyle(
+        regions,
+        &[(3..6, marked), (7..10, marked), (11..14, marked)],
+    )
+    .into_iter()
+    .map(|(style, text)| (text, style == marked))
+    .collect();
+    assert_eq!(
+        vec![
+            ("let", false),
+            ("•", true),
+            ("x", false),
+            ("•", true),
+            ("=", false),
+            ("•", true),
+            ("1;", false)
+        ],
+        texts
+    );
+}
+
+#[cfg(test)]
+fn with_test_printer<T>(
+    options: PrinterOptions,
+    text: &str,
+    print: impl FnOnce(&mut InteractivePrinter, InputFileReader) -> T,
+) -> T {
+    use std::collections::HashSet;
+    use syntect::highlighting::ThemeSet;
+    use syntect::parsing::SyntaxSetBuilder;
+
+    let mut builder = SyntaxSetBuilder::new();
+    builder.add_plain_text_syntax();
+    let mut theme_set = ThemeSet::default();
+    theme_set
+        .themes
+        .insert("test".to_string(), Theme::default());
+    let assets = HighlightingAssets {
+        syntax_set: builder.build(),
+        theme_set,
+    };
+
+    let file = InputFile::String(text.to_string());
+    let mut reader = file.get_reader().unwrap();
+    let mut printer = InteractivePrinter::new(
+        &assets,
+        &file,
+        &mut reader,
+        PrinterOptions {
+            output_components: OutputComponents(HashSet::new()),
+            theme: "test".to_string(),
+            term_width: 80,
+            ..options
+        },
+    );
+    print(&mut printer, reader)
+}
+
+#[cfg(test)]
+fn print_lines(options: PrinterOptions, text: &str) -> (String, PrintReport) {
+    with_test_printer(options, text, |printer, mut reader| {
+        let mut output = Vec::new();
+        let mut line = Vec::new();
+        let mut line_number = 1;
+        while reader.read_line(&mut line).unwrap() {
+            printer
+                .print_line(false, &mut output, line_number, &line)
+                .unwrap();
+            line.clear();
+            line_number += 1;
+        }
+        (String::from_utf8(output).unwrap(), printer.take_report())
+    })
+}
+
+/// The text of the output which has the given 256-color background.
+#[cfg(test)]
+fn text_with_background(output: &str, background: u8) -> String {
+    let escape = format!("\x1b[48;5;{};", background);
+    output
+        .split("\x1b[0m")
+        .filter_map(|text| text.split_once(&escape))
+        .map(|(_, text)| &text[text.find('m').unwrap() + 1..])
+        .collect()
+}
+
+#[test]
+fn test_long_lines() {
+    let options = PrinterOptions {
+        show_nonprintable: true,
+        safeguards: LineSafeguards {
+            max_line_length: Some(5),
+            long_lines: LongLines::Truncate,
+            highlight_timeout: None,
+        },
+        ..Default::default()
+    };
+
+    // Glyphs are longer than the characters they replace, but lines are
+    // measured in bytes of the input.
+    let (output, report) = print_lines(options, "a b c d\nx\u{200B}y\n  \n");
+    assert_eq!("a•b•c…␊\nx<U+200B>y␊\n••␊\n", output);
+    assert_eq!(vec![1], report.long_lines);
+}
+
+#[test]
+fn test_search_matches_with_glyphs() {
+    let options = PrinterOptions {
+        colored_output: true,
+        show_nonprintable: true,
+        highlight_pattern: Some(Regex::new(r"fn main|\t").unwrap()),
+        ..Default::default()
+    };
+    let (output, _) = print_lines(options, "pub fn main() {\n\tx\n");
+
+    // Matches are found in the input, and cover the glyphs in the output.
+    assert_eq!(
+        "fn•main├──┤",
+        text_with_background(&output, DEFAULT_SEARCH_MATCH_COLOR)
+    );
+}
+
+#[test]
+fn test_diff_emphasis_with_glyphs() {
+    let options = PrinterOptions {
+        colored_output: true,
+        show_nonprintable: true,
+        ..Default::default()
+    };
+    let line = DiffLine {
+        number: 1,
+        text: "\tlet  y = 2;\n".to_string(),
+        emphasis: vec![6..7, 10..12],
+    };
+
+    let output = with_test_printer(options, &line.text, |printer, _| {
+        let mut output = Vec::new();
+        printer
+            .print_diff_line(&mut output, false, &line, Some(LineChange::Added))
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    });
+    assert_eq!(
+        "y2;",
+        text_with_background(&output, DIFF_ADDED_EMPHASIS_COLOR)
+    );
+}
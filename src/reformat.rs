@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+
+/// Structured data formats which can be reformatted before printing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    Xml,
+}
+
+impl DataFormat {
+    /// Determines the data format from the name of a syntax.
+    pub fn from_syntax_name(name: &str) -> Option<DataFormat> {
+        match name.to_lowercase().as_ref() {
+            "json" => Some(DataFormat::Json),
+            "yaml" => Some(DataFormat::Yaml),
+            "toml" => Some(DataFormat::Toml),
+            "xml" => Some(DataFormat::Xml),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Yaml => "YAML",
+            DataFormat::Toml => "TOML",
+            DataFormat::Xml => "XML",
+        }
+    }
+}
+
+/// Parses `input` and writes it back with consistent indentation.
+///
+/// `indent_width` is not supported for YAML, which is always indented by two
+/// spaces, and for TOML it only applies to arrays. `sort_keys` has no effect
+/// on XML.
+pub fn reformat(
+    input: &str,
+    format: DataFormat,
+    indent_width: usize,
+    sort_keys: bool,
+) -> Result<String> {
+    let output = match format {
+        DataFormat::Json => reformat_json(input, indent_width, sort_keys),
+        DataFormat::Yaml => reformat_yaml(input, sort_keys),
+        DataFormat::Toml => reformat_toml(input, indent_width, sort_keys),
+        DataFormat::Xml => reformat_xml(input, indent_width),
+    };
+
+    output.map_err(|e| format!("Could not reformat {}: {}", format.name(), e).into())
+}
+
+type FormatResult = std::result::Result<String, Box<dyn std::error::Error>>;
+
+fn reformat_json(input: &str, indent_width: usize, sort_keys: bool) -> FormatResult {
+    let mut value: serde_json::Value = serde_json::from_str(input)?;
+    if sort_keys {
+        sort_json(&mut value);
+    }
+
+    let indent = " ".repeat(indent_width);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut output = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    value.serialize(&mut serializer)?;
+    output.push(b'\n');
+
+    Ok(String::from_utf8(output)?)
+}
+
+fn sort_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, mut value) in entries {
+                sort_json(&mut value);
+                map.insert(key, value);
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(sort_json),
+        _ => {}
+    }
+}
+
+fn reformat_yaml(input: &str, sort_keys: bool) -> FormatResult {
+    let mut documents = Vec::new();
+
+    for document in serde_yaml::Deserializer::from_str(input) {
+        let mut value = serde_yaml::Value::deserialize(document)?;
+        if sort_keys {
+            sort_yaml(&mut value);
+        }
+        documents.push(serde_yaml::to_string(&value)?);
+    }
+
+    Ok(documents.join("---\n"))
+}
+
+fn sort_yaml(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            let mut entries: Vec<_> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            for (key, mut value) in entries {
+                sort_yaml(&mut value);
+                map.insert(key, value);
+            }
+        }
+        serde_yaml::Value::Sequence(values) => values.iter_mut().for_each(sort_yaml),
+        serde_yaml::Value::Tagged(tagged) => sort_yaml(&mut tagged.value),
+        _ => {}
+    }
+}
+
+fn reformat_toml(input: &str, indent_width: usize, sort_keys: bool) -> FormatResult {
+    let mut value: toml::Value = input.parse()?;
+    if sort_keys {
+        sort_toml(&mut value);
+    }
+
+    let mut output = String::new();
+    let mut serializer = toml::Serializer::pretty(&mut output);
+    serializer.pretty_array_indent(indent_width);
+    value.serialize(&mut serializer)?;
+
+    Ok(output)
+}
+
+fn sort_toml(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            let mut entries: Vec<_> = std::mem::take(table).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, mut value) in entries {
+                sort_toml(&mut value);
+                table.insert(key, value);
+            }
+        }
+        toml::Value::Array(values) => values.iter_mut().for_each(sort_toml),
+        _ => {}
+    }
+}
+
+fn reformat_xml(input: &str, indent_width: usize) -> FormatResult {
+    let mut reader = Reader::from_str(input);
+    reader.trim_text(true);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', indent_width);
+
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    let mut output = writer.into_inner();
+    output.push(b'\n');
+    Ok(String::from_utf8(output)?)
+}
+
+#[test]
+fn test_reformat_json() {
+    let input = r#"{"b":1,"a":[true,null,{"d":"x","c":2.5}]}"#;
+
+    let expected = "{\n  \"b\": 1,\n  \"a\": [\n    true,\n    null,\n    {\n      \"d\": \"x\",\n      \"c\": 2.5\n    }\n  ]\n}\n";
+    assert_eq!(
+        expected,
+        reformat(input, DataFormat::Json, 2, false).unwrap()
+    );
+
+    let expected = "{\n    \"a\": [\n        true,\n        null,\n        {\n            \"c\": 2.5,\n            \"d\": \"x\"\n        }\n    ],\n    \"b\": 1\n}\n";
+    assert_eq!(
+        expected,
+        reformat(input, DataFormat::Json, 4, true).unwrap()
+    );
+
+    let error = reformat("{\"a\": }", DataFormat::Json, 2, false).unwrap_err();
+    assert!(error.to_string().starts_with("Could not reformat JSON"));
+}
+
+#[test]
+fn test_reformat_yaml() {
+    let input = "b: {y: 1, x: [1, 2]}\na: text\n";
+
+    assert_eq!(
+        "b:\n  y: 1\n  x:\n  - 1\n  - 2\na: text\n",
+        reformat(input, DataFormat::Yaml, 2, false).unwrap()
+    );
+    assert_eq!(
+        "a: text\nb:\n  x:\n  - 1\n  - 2\n  y: 1\n",
+        reformat(input, DataFormat::Yaml, 2, true).unwrap()
+    );
+    assert_eq!(
+        "a: 1\n---\nb: 2\n",
+        reformat("a: 1\n---\nb: 2\n", DataFormat::Yaml, 2, false).unwrap()
+    );
+    assert!(reformat("a: [1, 2", DataFormat::Yaml, 2, false).is_err());
+}
+
+#[test]
+fn test_reformat_toml() {
+    let input = "z = 1\na = [1, 2]\n[table]\nkey = \"value\"\n";
+
+    assert_eq!(
+        "z = 1\na = [\n    1,\n    2,\n]\n\n[table]\nkey = 'value'\n",
+        reformat(input, DataFormat::Toml, 4, false).unwrap()
+    );
+    assert_eq!(
+        "a = [\n  1,\n  2,\n]\nz = 1\n\n[table]\nkey = 'value'\n",
+        reformat(input, DataFormat::Toml, 2, true).unwrap()
+    );
+    assert!(reformat("a = ", DataFormat::Toml, 2, false).is_err());
+}
+
+#[test]
+fn test_reformat_xml() {
+    let input = "<?xml version=\"1.0\"?><note id=\"1\"><to>Tove</to><body/></note>";
+
+    assert_eq!(
+        "<?xml version=\"1.0\"?>\n<note id=\"1\">\n  <to>Tove</to>\n  <body/>\n</note>\n",
+        reformat(input, DataFormat::Xml, 2, false).unwrap()
+    );
+    assert!(reformat("<a><b></a>", DataFormat::Xml, 2, false).is_err());
+}
+
+#[test]
+fn test_format_from_syntax_name() {
+    assert_eq!(Some(DataFormat::Json), DataFormat::from_syntax_name("JSON"));
+    assert_eq!(Some(DataFormat::Toml), DataFormat::from_syntax_name("toml"));
+    assert_eq!(None, DataFormat::from_syntax_name("Rust"));
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use crate::audit::Finding;
+use crate::line_ending::split_line_ending;
+
+/// What to do with lines longer than the maximum line length.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LongLines {
+    /// Print the whole line without highlighting
+    #[default]
+    Plain,
+    /// Print the beginning of the line, followed by a marker
+    Truncate,
+}
+
+/// Limits which keep single lines from stalling the printer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineSafeguards {
+    /// Lines longer than this (in bytes) are not highlighted
+    pub max_line_length: Option<usize>,
+    pub long_lines: LongLines,
+    /// Highlighting is turned off for the rest of the input after a line
+    /// took longer than this to highlight
+    pub highlight_timeout: Option<Duration>,
+}
+
+/// The marker appended to truncated lines.
+pub const TRUNCATION_MARKER: &str = "…";
+
+/// Shortens the content of a line to at most `max_length` bytes (without
+/// breaking characters) and appends the truncation marker if anything was cut
+/// off. The line break is kept.
+pub fn truncate_line(line: &str, max_length: usize) -> String {
+    let (content, line_break) = split_line_ending(line);
+    let mut end = max_length.min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let marker = if end < content.len() {
+        TRUNCATION_MARKER
+    } else {
+        ""
+    };
+    format!("{}{}{}", &content[..end], marker, line_break)
+}
+
+/// Limits the amount of output for one input.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    max_bytes: Option<usize>,
+    max_lines: Option<usize>,
+    bytes: usize,
+    lines: usize,
+}
+
+impl Budget {
+    pub fn new(max_bytes: Option<usize>, max_lines: Option<usize>) -> Self {
+        Budget {
+            max_bytes,
+            max_lines,
+            ..Budget::default()
+        }
+    }
+
+    /// Accounts for a printed line. Returns `false` if the line exceeds the
+    /// budget and should not be printed anymore.
+    pub fn consume(&mut self, line: &[u8]) -> bool {
+        let bytes = self.bytes + line.len();
+        let lines = self.lines + 1;
+
+        if self.max_bytes.is_some_and(|max| bytes > max)
+            || self.max_lines.is_some_and(|max| lines > max)
+        {
+            return false;
+        }
+
+        self.bytes = bytes;
+        self.lines = lines;
+        true
+    }
+}
+
+/// Describes which safeguards took effect while printing an input, and what
+/// the audit found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrintReport {
+    /// Lines which were not highlighted (or truncated) because they exceeded
+    /// the maximum line length
+    pub long_lines: Vec<usize>,
+    /// The line after which highlighting was turned off because it exceeded
+    /// the highlighting timeout
+    pub slow_line: Option<usize>,
+    /// The last printed line, if output stopped because the budget was
+    /// exhausted
+    pub truncated_after: Option<usize>,
+    /// Suspicious content in the printed lines, if the input was audited
+    pub findings: Vec<Finding>,
+}
+
+#[test]
+fn test_truncate_line() {
+    assert_eq!("abc…\n", truncate_line("abcdef\n", 3));
+    assert_eq!("abc…\r\n", truncate_line("abcdef\r\n", 3));
+    assert_eq!("a…", truncate_line("aäb", 2));
+    assert_eq!("abc", truncate_line("abc", 10));
+    assert_eq!("abc\r\n", truncate_line("abc\r\n", 3));
+}
+
+#[test]
+fn test_budget() {
+    let mut unlimited = Budget::default();
+    assert!((0..1000).all(|_| unlimited.consume(b"line\n")));
+
+    let mut lines = Budget::new(None, Some(2));
+    assert!(lines.consume(b"a\n"));
+    assert!(lines.consume(b"b\n"));
+    assert!(!lines.consume(b"c\n"));
+
+    let mut bytes = Budget::new(Some(10), None);
+    assert!(bytes.consume(b"12345\n"));
+    assert!(!bytes.consume(b"12345\n"));
+}
@@ -0,0 +1,58 @@
+use lazy_static::lazy_static;
+use syntect::parsing::{Scope, ScopeStack, ScopeStackOp};
+
+lazy_static! {
+    static ref STRING_OR_COMMENT: Vec<Scope> = vec![
+        Scope::new("string").unwrap(),
+        Scope::new("comment").unwrap(),
+    ];
+    static ref PUNCTUATION: Scope = Scope::new("punctuation").unwrap();
+    static ref INVALID: Scope = Scope::new("invalid").unwrap();
+}
+
+/// The scopes of the character at `offset` in a line, from the scopes at the
+/// beginning of the line and the operations of the parser for the line.
+pub fn scopes_at(start: &ScopeStack, ops: &[(usize, ScopeStackOp)], offset: usize) -> ScopeStack {
+    let mut stack = start.clone();
+    for (_, op) in ops.iter().take_while(|(position, _)| *position <= offset) {
+        stack.apply(op).ok();
+    }
+    stack
+}
+
+pub fn in_string_or_comment(stack: &ScopeStack) -> bool {
+    stack.as_slice().iter().any(|&scope| {
+        STRING_OR_COMMENT
+            .iter()
+            .any(|outer| outer.is_prefix_of(scope))
+    })
+}
+
+/// Whether the innermost scope is punctuation, like the brackets of a block.
+pub fn is_punctuation(stack: &ScopeStack) -> bool {
+    stack
+        .as_slice()
+        .last()
+        .is_some_and(|&scope| PUNCTUATION.is_prefix_of(scope))
+}
+
+/// Whether the innermost scope is invalid code, like a stray bracket.
+pub fn is_invalid(stack: &ScopeStack) -> bool {
+    stack
+        .as_slice()
+        .last()
+        .is_some_and(|&scope| INVALID.is_prefix_of(scope))
+}
+
+#[test]
+fn test_scopes_at() {
+    let start = ScopeStack::from_vec(vec![Scope::new("source.python").unwrap()]);
+    let ops = vec![
+        (4, ScopeStackOp::Push(Scope::new("comment.block").unwrap())),
+        (7, ScopeStackOp::Pop(1)),
+    ];
+
+    assert!(!in_string_or_comment(&scopes_at(&start, &ops, 3)));
+    assert!(in_string_or_comment(&scopes_at(&start, &ops, 4)));
+    assert!(!in_string_or_comment(&scopes_at(&start, &ops, 7)));
+}
@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::errors::*;
+
+/// Combines a regular expression and a list of literals into a single
+/// pattern. Returns `None` if there is nothing to search for.
+pub fn build_pattern(pattern: Option<&Regex>, literals: &[String]) -> Result<Option<Regex>> {
+    let alternatives: Vec<String> = pattern
+        .map(|pattern| format!("(?:{})", pattern.as_str()))
+        .into_iter()
+        .chain(
+            literals
+                .iter()
+                .filter(|l| !l.is_empty())
+                .map(|l| regex::escape(l)),
+        )
+        .collect();
+
+    if alternatives.is_empty() {
+        return Ok(None);
+    }
+
+    Regex::new(&alternatives.join("|"))
+        .map(Some)
+        .map_err(|e| format!("Invalid highlight pattern: {}", e).into())
+}
+
+/// Finds the byte ranges of all (non-empty) matches within a line.
+pub fn find_matches(pattern: &Regex, line: &str) -> Vec<Range<usize>> {
+    pattern
+        .find_iter(line)
+        .map(|m| m.range())
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// Whether a line is printed or only passed to the highlighter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterAction {
+    Show,
+    Hide,
+}
+
+/// Decides which lines are shown when only matching lines and some lines of
+/// context around them should be printed.
+///
+/// Lines are buffered until it is known whether they are context of a later
+/// match, but always come out in their original order.
+#[derive(Debug)]
+pub struct ContextFilter {
+    context: usize,
+    pending: VecDeque<(usize, Vec<u8>)>,
+    after: usize,
+}
+
+impl ContextFilter {
+    pub fn new(context: usize) -> Self {
+        ContextFilter {
+            context,
+            pending: VecDeque::new(),
+            after: 0,
+        }
+    }
+
+    /// Adds a line. Lines which are not `in_range` are never shown, not even
+    /// as context.
+    pub fn push(
+        &mut self,
+        line_number: usize,
+        line: Vec<u8>,
+        is_match: bool,
+        in_range: bool,
+    ) -> Vec<(FilterAction, usize, Vec<u8>)> {
+        let mut output = Vec::new();
+
+        if !in_range {
+            output.extend(self.flush(FilterAction::Hide));
+            output.push((FilterAction::Hide, line_number, line));
+            self.after = 0;
+        } else if is_match {
+            output.extend(self.flush(FilterAction::Show));
+            output.push((FilterAction::Show, line_number, line));
+            self.after = self.context;
+        } else if self.after > 0 {
+            output.push((FilterAction::Show, line_number, line));
+            self.after -= 1;
+        } else {
+            self.pending.push_back((line_number, line));
+            if self.pending.len() > self.context {
+                let (number, line) = self.pending.pop_front().unwrap();
+                output.push((FilterAction::Hide, number, line));
+            }
+        }
+
+        output
+    }
+
+    /// Returns the remaining lines, which are not followed by a match.
+    pub fn finish(&mut self) -> Vec<(FilterAction, usize, Vec<u8>)> {
+        self.flush(FilterAction::Hide)
+    }
+
+    fn flush(&mut self, action: FilterAction) -> Vec<(FilterAction, usize, Vec<u8>)> {
+        self.pending
+            .drain(..)
+            .map(|(number, line)| (action, number, line))
+            .collect()
+    }
+}
+
+#[test]
+fn test_build_pattern() {
+    assert!(build_pattern(None, &[]).unwrap().is_none());
+
+    let regex = Regex::new(r"fo+").unwrap();
+    let literals = vec!["a.b".to_string(), "".to_string()];
+    let pattern = build_pattern(Some(&regex), &literals).unwrap().unwrap();
+    assert_eq!(r"(?:fo+)|a\.b", pattern.as_str());
+    assert_eq!(vec![0..4, 5..8], find_matches(&pattern, "fooo a.b axb"));
+}
+
+#[test]
+fn test_find_matches_skips_empty_matches() {
+    let pattern = Regex::new("x*").unwrap();
+    assert_eq!(vec![1..3], find_matches(&pattern, "axxb"));
+}
+
+#[cfg(test)]
+fn filter(context: usize, lines: &[(bool, bool)]) -> Vec<(FilterAction, usize)> {
+    let mut filter = ContextFilter::new(context);
+    let mut output = Vec::new();
+    for (i, &(is_match, in_range)) in lines.iter().enumerate() {
+        output.extend(filter.push(i + 1, vec![], is_match, in_range));
+    }
+    output.extend(filter.finish());
+    output
+        .into_iter()
+        .map(|(action, n, _)| (action, n))
+        .collect()
+}
+
+#[test]
+fn test_context_filter() {
+    use self::FilterAction::*;
+
+    let lines = [
+        (false, true),
+        (false, true),
+        (false, true),
+        (true, true),
+        (false, true),
+        (false, true),
+        (false, true),
+    ];
+    assert_eq!(
+        vec![
+            (Hide, 1),
+            (Hide, 2),
+            (Show, 3),
+            (Show, 4),
+            (Show, 5),
+            (Hide, 6),
+            (Hide, 7),
+        ],
+        filter(1, &lines)
+    );
+    assert_eq!(
+        vec![
+            (Hide, 1),
+            (Hide, 2),
+            (Hide, 3),
+            (Show, 4),
+            (Hide, 5),
+            (Hide, 6),
+            (Hide, 7),
+        ],
+        filter(0, &lines)
+    );
+}
+
+#[test]
+fn test_context_filter_out_of_range() {
+    use self::FilterAction::*;
+
+    let lines = [(false, false), (false, true), (true, true), (false, false)];
+    assert_eq!(
+        vec![(Hide, 1), (Show, 2), (Show, 3), (Hide, 4)],
+        filter(2, &lines)
+    );
+}
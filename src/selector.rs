@@ -0,0 +1,422 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use regex::Regex;
+use syntect::easy::ScopeRangeIterator;
+use syntect::highlighting::ScopeSelectors;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+
+use crate::errors::*;
+use crate::line_range::LineRange;
+use crate::scopes::in_string_or_comment;
+
+/// Selects the lines of a code structure, like a function, instead of
+/// explicit line numbers.
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// The definition of a symbol with the given name. The symbol is found by
+    /// the scope of its name (e.g. `entity.name.function`).
+    Symbol { name: String, scope: String },
+
+    /// The lines from the first match of `start` to the next match of `end`.
+    /// Without `end`, the block starting at `start` is selected.
+    Pattern { start: Regex, end: Option<Regex> },
+}
+
+impl Selector {
+    /// Selects any named item (functions, types, ...).
+    pub fn symbol<T: Into<String>>(name: T) -> Selector {
+        Selector::Symbol {
+            name: name.into(),
+            scope: "entity.name".to_string(),
+        }
+    }
+
+    /// Selects a function.
+    pub fn function<T: Into<String>>(name: T) -> Selector {
+        Selector::Symbol {
+            name: name.into(),
+            scope: "entity.name.function".to_string(),
+        }
+    }
+
+    /// Selects the lines between two patterns.
+    pub fn pattern(start: Regex, end: Option<Regex>) -> Selector {
+        Selector::Pattern { start, end }
+    }
+
+    /// Finds the selected lines within `lines`. The returned range is named
+    /// after the selector.
+    pub fn select(
+        &self,
+        lines: &[String],
+        syntax: Option<&SyntaxReference>,
+        syntax_set: &SyntaxSet,
+    ) -> Result<LineRange> {
+        let (name, start, end) = match self {
+            Selector::Symbol { name, scope } => {
+                let start = syntax
+                    .and_then(|syntax| find_symbol(lines, name, scope, syntax, syntax_set))
+                    .ok_or_else(|| format!("Could not find symbol '{}'", name))?;
+                (
+                    name.clone(),
+                    start,
+                    block_end(lines, start, syntax, syntax_set),
+                )
+            }
+            Selector::Pattern { start, end } => {
+                let first = lines
+                    .iter()
+                    .position(|line| start.is_match(line))
+                    .ok_or_else(|| format!("Could not find pattern '{}'", start))?;
+                let last = match end {
+                    // The end is searched after the start, even if the start
+                    // matches it too.
+                    Some(end) => (first + 1..lines.len())
+                        .find(|&i| end.is_match(&lines[i]))
+                        .ok_or_else(|| format!("Could not find pattern '{}'", end))?,
+                    None => block_end(lines, first, syntax, syntax_set),
+                };
+                (start.to_string(), first, last)
+            }
+        };
+
+        let mut range = LineRange::new();
+        range.lower = start + 1;
+        range.upper = end + 1;
+        range.name = Some(name);
+        Ok(range)
+    }
+}
+
+/// Finds the index of the line where a token with the given text and scope
+/// occurs first.
+fn find_symbol(
+    lines: &[String],
+    name: &str,
+    scope: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+) -> Option<usize> {
+    let selector = ScopeSelectors::from_str(scope).ok()?;
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let ops = state.parse_line(line, syntax_set).ok()?;
+        for (range, op) in ScopeRangeIterator::new(&ops, line) {
+            stack.apply(op).ok()?;
+            if line[range].trim() == name && selector.does_match(stack.as_slice()).is_some() {
+                return Some(index);
+            }
+        }
+    }
+
+    None
+}
+
+/// Finds the index of the last line of the block starting at `start`.
+///
+/// Blocks delimited by braces end where the braces are balanced again.
+/// Otherwise (e.g. in Python), the block contains all following lines that
+/// are indented further than the first one. With a syntax, braces in strings
+/// and comments are found by their scopes, otherwise by common delimiters.
+pub fn block_end(
+    lines: &[String],
+    start: usize,
+    syntax: Option<&SyntaxReference>,
+    syntax_set: &SyntaxSet,
+) -> usize {
+    let end = match syntax {
+        Some(syntax) => bracket_block_end(code_lines(lines, start, syntax, syntax_set), start),
+        None => bracket_block_end(
+            lines[start..].iter().map(|line| Cow::from(line.as_str())),
+            start,
+        ),
+    };
+    end.unwrap_or_else(|| indentation_block_end(lines, start))
+}
+
+/// The lines from `start` on, with the characters of strings and comments
+/// replaced by spaces. Lines which can't be parsed are left unchanged.
+fn code_lines<'a>(
+    lines: &'a [String],
+    start: usize,
+    syntax: &SyntaxReference,
+    syntax_set: &'a SyntaxSet,
+) -> impl Iterator<Item = Cow<'a, str>> + 'a {
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+
+    lines.iter().enumerate().filter_map(move |(index, line)| {
+        let ops = match state.parse_line(line, syntax_set) {
+            Ok(ops) => ops,
+            Err(_) => return (index >= start).then(|| Cow::from(line.as_str())),
+        };
+        if index < start {
+            for (_, op) in &ops {
+                stack.apply(op).ok();
+            }
+            return None;
+        }
+
+        let mut ops = ops.iter().peekable();
+        let mut code = String::with_capacity(line.len());
+        for (offset, c) in line.char_indices() {
+            while let Some((_, op)) = ops.next_if(|(position, _)| *position <= offset) {
+                stack.apply(op).ok();
+            }
+            let masked = c != '\n' && in_string_or_comment(&stack);
+            code.push(if masked { ' ' } else { c });
+        }
+        for (_, op) in ops {
+            stack.apply(op).ok();
+        }
+        Some(Cow::from(code))
+    })
+}
+
+/// The number of lines after the start of a block in which its opening brace
+/// is searched, e.g. for long function signatures.
+const MAX_SIGNATURE_LINES: usize = 10;
+
+fn bracket_block_end<'a>(lines: impl Iterator<Item = Cow<'a, str>>, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut nesting = 0;
+    let mut opened = false;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut block_comment = false;
+
+    for (index, line) in lines.enumerate().map(|(i, line)| (start + i, line)) {
+        if !opened && index > start + MAX_SIGNATURE_LINES {
+            return None;
+        }
+
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let char_at = |i: usize| chars.get(i).map(|&(_, c)| c);
+        // The end of the code in the line, before a trailing comment
+        let mut code_end = line.len();
+        let mut i = 0;
+        while let Some(c) = char_at(i) {
+            i += 1;
+            if block_comment {
+                if c == '*' && char_at(i) == Some('/') {
+                    block_comment = false;
+                    i += 1;
+                }
+                continue;
+            }
+            if let Some(q) = quote {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    _ if c == q => quote = None,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match c {
+                '"' | '`' => quote = Some(c),
+                // Single quotes are also used for lifetimes, so only quote
+                // character literals like `'{'` and `'\''`.
+                '\'' if char_at(i + 1) == Some('\'') || char_at(i) == Some('\\') => quote = Some(c),
+                '/' if char_at(i) == Some('*') => {
+                    block_comment = true;
+                    i += 1;
+                }
+                // Line comments, e.g. `// }` or `# }`, but not attributes
+                // like `#[test]`.
+                '/' if char_at(i) == Some('/') => {
+                    code_end = chars[i - 1].0;
+                    break;
+                }
+                '#' if char_at(i).is_none_or(char::is_whitespace) => {
+                    code_end = chars[i - 1].0;
+                    break;
+                }
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => {
+                    depth -= 1;
+                    if opened && depth == 0 {
+                        return Some(index);
+                    }
+                }
+                '(' | '[' => nesting += 1,
+                ')' | ']' => nesting -= 1,
+                // A declaration without a body, e.g. `fn f();`
+                ';' if !opened && nesting == 0 => return Some(index),
+                _ => {}
+            }
+        }
+
+        // Quotes don't span lines, so an apostrophe in a comment doesn't
+        // swallow the rest of the block.
+        quote = None;
+        escaped = false;
+
+        // A line ending with a colon starts an indented block (e.g. Python).
+        if !opened && line[..code_end].trim_end().ends_with(':') {
+            return None;
+        }
+    }
+
+    None
+}
+
+fn indentation_block_end(lines: &[String], start: usize) -> usize {
+    let indentation = |line: &str| line.len() - line.trim_start().len();
+    let base = indentation(&lines[start]);
+
+    let mut end = start;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indentation(line) <= base {
+            break;
+        }
+        end = index;
+    }
+
+    end
+}
+
+#[cfg(test)]
+fn lines(code: &str) -> Vec<String> {
+    code.lines().map(|line| format!("{}\n", line)).collect()
+}
+
+#[test]
+fn test_block_end_braces() {
+    let code = lines(
+        "fn a() {
+    let s = \"}\";
+    let c = '{';
+    if true {
+        b();
+    }
+}
+
+fn c<'a>(x: &'a str)
+    -> usize
+{
+    0
+}
+fn d(x: [u8; 4]);",
+    );
+
+    assert_eq!(6, block_end(&code, 0, None, &SyntaxSet::new()));
+    assert_eq!(12, block_end(&code, 8, None, &SyntaxSet::new()));
+    assert_eq!(13, block_end(&code, 13, None, &SyntaxSet::new()));
+}
+
+#[test]
+fn test_block_end_comments() {
+    let code = lines(
+        "fn a() { // {
+    /* } */
+    b(); /*
+    } */
+}
+fn c(); // ;
+# {
+def d(): # :
+    pass",
+    );
+    let syntax_set = SyntaxSet::new();
+
+    assert_eq!(4, block_end(&code, 0, None, &syntax_set));
+    assert_eq!(5, block_end(&code, 5, None, &syntax_set));
+    assert_eq!(8, block_end(&code, 7, None, &syntax_set));
+}
+
+#[test]
+fn test_block_end_scopes() {
+    use syntect::parsing::{SyntaxDefinition, SyntaxSetBuilder};
+
+    // Strings and comments are only known by their scopes, e.g. `--` and
+    // single-quoted strings in SQL-like code.
+    let definition = r#"
+name: Scoped
+scope: source.scoped
+file_extensions: [scoped]
+contexts:
+  main:
+    - match: "--.*$"
+      scope: comment.line
+    - match: "'[^']*'"
+      scope: string.quoted
+"#;
+    let mut builder = SyntaxSetBuilder::new();
+    builder.add(SyntaxDefinition::load_from_str(definition, true, None).unwrap());
+    let syntax_set = builder.build();
+    let syntax = syntax_set.find_syntax_by_name("Scoped");
+
+    let code = lines(
+        "a {
+  -- }
+  b('}', '--');
+}",
+    );
+
+    assert_eq!(3, block_end(&code, 0, syntax, &syntax_set));
+    assert_eq!(1, block_end(&code, 0, None, &syntax_set));
+}
+
+#[test]
+fn test_block_end_indentation() {
+    let code = lines(
+        "def a():
+    x = {'a': 1}
+
+    return x
+
+def b():
+    pass",
+    );
+
+    assert_eq!(3, block_end(&code, 0, None, &SyntaxSet::new()));
+    assert_eq!(6, block_end(&code, 5, None, &SyntaxSet::new()));
+}
+
+#[test]
+fn test_select_pattern() {
+    let code = lines("a\nBEGIN\nb\nEND\nc\n");
+    let selector = Selector::pattern(
+        Regex::new("^BEGIN").unwrap(),
+        Some(Regex::new("^END").unwrap()),
+    );
+
+    let range = selector.select(&code, None, &SyntaxSet::new()).unwrap();
+    assert_eq!(2, range.lower);
+    assert_eq!(4, range.upper);
+    assert_eq!(Some("^BEGIN".to_string()), range.name);
+
+    let selector = Selector::pattern(Regex::new("^MISSING").unwrap(), None);
+    assert!(selector.select(&code, None, &SyntaxSet::new()).is_err());
+}
+
+#[test]
+fn test_select_pattern_matching_start_and_end() {
+    let code = lines("a\n---\nb: 1\n---\nc\n");
+    let separator = Regex::new("^---").unwrap();
+    let selector = Selector::pattern(separator.clone(), Some(separator));
+
+    let range = selector.select(&code, None, &SyntaxSet::new()).unwrap();
+    assert_eq!(2, range.lower);
+    assert_eq!(4, range.upper);
+}
+
+#[test]
+fn test_select_symbol_without_syntax() {
+    let code = lines("fn fib() {}\n");
+    let error = Selector::function("fib")
+        .select(&code, None, &SyntaxSet::new())
+        .unwrap_err();
+    assert_eq!("Could not find symbol 'fib'", error.to_string());
+}
@@ -55,6 +55,10 @@ impl Default for OutputComponents {
 }
 
 impl OutputComponents {
+    pub fn changes(&self) -> bool {
+        self.0.contains(&OutputComponent::Changes)
+    }
+
     pub fn grid(&self) -> bool {
         self.0.contains(&OutputComponent::Grid)
     }
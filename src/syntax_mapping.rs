@@ -1,41 +1,221 @@
-use std::borrow::Cow;
-use std::collections::HashMap;
+use std::path::Path;
 
+use globset::{Candidate, GlobBuilder, GlobMatcher};
+
+use crate::errors::*;
+
+/// The target of a syntax mapping rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MappingTarget {
+    /// Use the syntax with the given name or token (e.g. `"toml"` or `"YAML"`).
+    MapTo(String),
+
+    /// Treat matching files as if no syntax could be detected, i.e. fall back
+    /// to first-line detection and eventually to plain text.
+    MapToUnknown,
+}
+
+/// Maps file paths onto syntaxes.
+///
+/// Rules are glob patterns (see `globset`) which are matched against the full
+/// path of the input as well as against its bare file name, so both
+/// `"Cargo.lock"` and `"/etc/nginx/**/*.conf"` are valid patterns.
+///
+/// Rules inserted later take precedence over rules inserted earlier. Since
+/// `SyntaxMapping::builtin()` inserts the built-in rules first, user-defined
+/// rules always win over the defaults.
 #[derive(Debug, Clone)]
-pub struct SyntaxMapping(HashMap<String, String>);
+pub struct SyntaxMapping {
+    mappings: Vec<(GlobMatcher, MappingTarget)>,
+    ignored_suffixes: Vec<String>,
+}
 
 impl Default for SyntaxMapping {
     fn default() -> Self {
-        SyntaxMapping(HashMap::new())
+        SyntaxMapping::new()
     }
 }
 
 impl SyntaxMapping {
+    /// Creates a mapping without any rules or ignored suffixes.
     pub fn new() -> SyntaxMapping {
-        SyntaxMapping(HashMap::new())
+        SyntaxMapping {
+            mappings: vec![],
+            ignored_suffixes: vec![],
+        }
     }
 
-    pub fn insert(&mut self, from: String, to: String) -> Option<String> {
-        self.0.insert(from, to)
-    }
+    /// Creates a mapping with the built-in rules and ignored suffixes.
+    pub fn builtin() -> SyntaxMapping {
+        let mut mapping = SyntaxMapping::new();
+
+        for suffix in &[
+            "~",
+            ".bak",
+            ".old",
+            ".orig",
+            ".in",
+            ".dpkg-dist",
+            ".dpkg-old",
+            ".rpmnew",
+            ".rpmorig",
+            ".rpmsave",
+        ] {
+            mapping.insert_ignored_suffix(*suffix);
+        }
 
-    pub fn replace<'a>(&self, input: &'a str) -> Cow<'a, str> {
-        let mut out = Cow::from(input);
-        if let Some(value) = self.0.get(input) {
-            out = Cow::from(value.clone())
+        for (from, to) in &[
+            ("*.h", "C++"),
+            ("*.fs", "F#"),
+            ("Cargo.lock", "TOML"),
+            ("Pipfile.lock", "JSON"),
+            ("Dockerfile.*", "Dockerfile"),
+            ("*.dockerfile", "Dockerfile"),
+            ("**/.github/workflows/*.yml", "YAML"),
+            ("**/.github/workflows/*.yaml", "YAML"),
+            ("/etc/nginx/**/*.conf", "nginx"),
+            ("/etc/nginx/sites-*/**/*", "nginx"),
+            ("/etc/apache2/**/*.conf", "Apache Conf"),
+            ("**/.ssh/config", "SSH Config"),
+            ("/etc/profile", "Bourne Again Shell (bash)"),
+            ("*.ksh", "Bourne Again Shell (bash)"),
+        ] {
+            mapping
+                .insert(from, *to)
+                .expect("built-in syntax mappings are valid globs");
         }
-        out
+
+        mapping
+            .insert_rule("*.txt", MappingTarget::MapToUnknown)
+            .expect("built-in syntax mappings are valid globs");
+
+        mapping
+    }
+
+    /// Maps all files matching the glob pattern `from` onto the syntax `to`.
+    ///
+    /// Returns an error if `from` is not a valid glob pattern.
+    pub fn insert<F: AsRef<str>, T: Into<String>>(&mut self, from: F, to: T) -> Result<()> {
+        self.insert_rule(from.as_ref(), MappingTarget::MapTo(to.into()))
+    }
+
+    /// Adds a rule with an arbitrary `MappingTarget`.
+    pub fn insert_rule(&mut self, from: &str, to: MappingTarget) -> Result<()> {
+        let glob = GlobBuilder::new(from)
+            .case_insensitive(false)
+            .literal_separator(true)
+            .build()
+            .chain_err(|| format!("Invalid syntax mapping pattern '{}'", from))?;
+        self.mappings.push((glob.compile_matcher(), to));
+        Ok(())
+    }
+
+    /// Adds a file name suffix (like `".bak"`) that gets stripped before the
+    /// syntax is detected.
+    pub fn insert_ignored_suffix<T: Into<String>>(&mut self, suffix: T) {
+        self.ignored_suffixes.push(suffix.into());
+    }
+
+    /// Returns the mapping target of the rule with the highest precedence
+    /// that matches `path`, if any.
+    pub fn get_syntax_for<P: AsRef<Path>>(&self, path: P) -> Option<&MappingTarget> {
+        let path = path.as_ref();
+        let candidate = Candidate::new(path);
+        let candidate_filename = path.file_name().map(Candidate::new);
+
+        self.mappings
+            .iter()
+            .rev()
+            .find(|(glob, _)| {
+                glob.is_match_candidate(&candidate)
+                    || candidate_filename
+                        .as_ref()
+                        .is_some_and(|filename| glob.is_match_candidate(filename))
+            })
+            .map(|(_, target)| target)
+    }
+
+    /// Strips one of the ignored suffixes from `file_name`, if present.
+    pub fn strip_ignored_suffix<'a>(&self, file_name: &'a str) -> Option<&'a str> {
+        self.ignored_suffixes
+            .iter()
+            .filter(|suffix| file_name.len() > suffix.len())
+            .find_map(|suffix| file_name.strip_suffix(suffix.as_str()))
     }
 }
 
 #[test]
 fn basic() {
     let mut map = SyntaxMapping::new();
-    map.insert("Cargo.lock".into(), "toml".into());
-    map.insert(".ignore".into(), ".gitignore".into());
+    map.insert("Cargo.lock", "toml").unwrap();
+    map.insert(".ignore", ".gitignore").unwrap();
+
+    assert_eq!(
+        Some(&MappingTarget::MapTo("toml".into())),
+        map.get_syntax_for("Cargo.lock")
+    );
+    assert_eq!(None, map.get_syntax_for("other.lock"));
+
+    assert_eq!(
+        Some(&MappingTarget::MapTo(".gitignore".into())),
+        map.get_syntax_for("/home/user/.ignore")
+    );
+}
+
+#[test]
+fn globs() {
+    let mut map = SyntaxMapping::new();
+    map.insert("/etc/nginx/**/*.conf", "nginx").unwrap();
+    map.insert("Dockerfile.*", "Dockerfile").unwrap();
+    map.insert("**/.github/workflows/*.yml", "YAML").unwrap();
+
+    assert_eq!(
+        Some(&MappingTarget::MapTo("nginx".into())),
+        map.get_syntax_for("/etc/nginx/sites-enabled/default.conf")
+    );
+    assert_eq!(None, map.get_syntax_for("/etc/other/default.conf"));
+    assert_eq!(
+        Some(&MappingTarget::MapTo("Dockerfile".into())),
+        map.get_syntax_for("build/Dockerfile.release")
+    );
+    assert_eq!(
+        Some(&MappingTarget::MapTo("YAML".into())),
+        map.get_syntax_for("project/.github/workflows/ci.yml")
+    );
+    assert_eq!(None, map.get_syntax_for("project/.github/ci.yml"));
 
-    assert_eq!("toml", map.replace("Cargo.lock"));
-    assert_eq!("other.lock", map.replace("other.lock"));
+    assert!(map.insert("[unclosed", "nothing").is_err());
+}
+
+#[test]
+fn precedence() {
+    let mut map = SyntaxMapping::builtin();
+    assert_eq!(
+        Some(&MappingTarget::MapTo("TOML".into())),
+        map.get_syntax_for("Cargo.lock")
+    );
+    assert_eq!(
+        Some(&MappingTarget::MapToUnknown),
+        map.get_syntax_for("notes.txt")
+    );
+
+    map.insert("*.lock", "JSON").unwrap();
+    assert_eq!(
+        Some(&MappingTarget::MapTo("JSON".into())),
+        map.get_syntax_for("Cargo.lock")
+    );
+}
+
+#[test]
+fn ignored_suffixes() {
+    let map = SyntaxMapping::builtin();
 
-    assert_eq!(".gitignore", map.replace(".ignore"));
+    assert_eq!(Some("main.rs"), map.strip_ignored_suffix("main.rs.bak"));
+    assert_eq!(
+        Some("config.toml"),
+        map.strip_ignored_suffix("config.toml~")
+    );
+    assert_eq!(Some("Makefile"), map.strip_ignored_suffix("Makefile.in"));
+    assert_eq!(None, map.strip_ignored_suffix("main.rs"));
+    assert_eq!(None, map.strip_ignored_suffix(".bak"));
 }
@@ -18,13 +18,14 @@ pub fn as_terminal_escaped(
     true_color: bool,
     colored: bool,
     italics: bool,
+    background: Option<ansi_term::Colour>,
 ) -> String {
     let style = if !colored {
         Style::default()
     } else {
         let color = to_ansi_color(style.foreground, true_color);
 
-        if style.font_style.contains(FontStyle::BOLD) {
+        let style = if style.font_style.contains(FontStyle::BOLD) {
             color.bold()
         } else if style.font_style.contains(FontStyle::UNDERLINE) {
             color.underline()
@@ -32,6 +33,11 @@ pub fn as_terminal_escaped(
             color.italic()
         } else {
             color.normal()
+        };
+
+        match background {
+            Some(background) => style.on(background),
+            None => style,
         }
     };
 
@@ -0,0 +1,68 @@
+use serde::Serialize;
+
+use crate::errors::*;
+
+/// Serialization formats for `PrettyPrint::value`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Ron,
+}
+
+impl Format {
+    /// The language used to highlight the serialized value.
+    pub(crate) fn language(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            // There is no RON syntax, but Rust comes close.
+            Format::Ron => "rust",
+        }
+    }
+
+    /// Serializes `value` into a human readable string.
+    pub(crate) fn serialize<T: Serialize + ?Sized>(self, value: &T) -> Result<String> {
+        let output = match self {
+            Format::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+            Format::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            Format::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+                .map_err(|e| e.to_string()),
+        };
+
+        output.map_err(|e| format!("Could not serialize value as {:?}: {}", self, e).into())
+    }
+}
+
+#[test]
+fn test_serialize() {
+    use std::collections::BTreeMap;
+
+    let mut value = BTreeMap::new();
+    value.insert("name", vec![1, 2]);
+
+    assert_eq!(
+        "{\n  \"name\": [\n    1,\n    2\n  ]\n}",
+        Format::Json.serialize(&value).unwrap()
+    );
+    assert_eq!("name:\n- 1\n- 2\n", Format::Yaml.serialize(&value).unwrap());
+    assert_eq!(
+        "name = [\n    1,\n    2,\n]\n",
+        Format::Toml.serialize(&value).unwrap()
+    );
+    assert_eq!(
+        "{\n    \"name\": [\n        1,\n        2,\n    ],\n}",
+        Format::Ron.serialize(&value).unwrap()
+    );
+}
+
+#[test]
+fn test_serialize_error() {
+    let error = Format::Toml.serialize(&None::<i32>).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Could not serialize value as Toml"));
+}
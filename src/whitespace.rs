@@ -0,0 +1,205 @@
+use std::cmp::Reverse;
+use std::ops::Range;
+
+use crate::line_ending::split_line_ending;
+
+/// The character which indents the lines of an input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces,
+}
+
+impl IndentStyle {
+    /// Detects the indentation of most of the lines in `text`. Single spaces
+    /// are ignored, since they often align comments. Returns `None` if no
+    /// line is indented.
+    pub fn detect(text: &str) -> Option<IndentStyle> {
+        let (mut tabs, mut spaces) = (0, 0);
+        for line in text.lines() {
+            if line.starts_with('\t') {
+                tabs += 1;
+            } else if line.starts_with("  ") {
+                spaces += 1;
+            }
+        }
+
+        match (tabs, spaces) {
+            (0, 0) => None,
+            (tabs, spaces) if tabs >= spaces => Some(IndentStyle::Tabs),
+            _ => Some(IndentStyle::Spaces),
+        }
+    }
+}
+
+/// Detects the width of an indentation level in `text`, from the lines
+/// indented with spaces: it's the most common increase of the indentation
+/// from one line to the next.
+pub fn detect_indent_width(text: &str) -> Option<usize> {
+    let mut increases = [0; 9];
+    let mut previous = 0;
+    for line in text.lines() {
+        if line.trim().is_empty() || line.starts_with('\t') {
+            continue;
+        }
+
+        let indentation = line.len() - line.trim_start_matches(' ').len();
+        if let Some(increase) = indentation
+            .checked_sub(previous)
+            .filter(|i| (2..=8).contains(i))
+        {
+            increases[increase] += 1;
+        }
+        previous = indentation;
+    }
+
+    // Ties go to the narrower width.
+    (2..=8)
+        .max_by_key(|&width| (increases[width], Reverse(width)))
+        .filter(|&width| increases[width] > 0)
+}
+
+/// How indentation guides are drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IndentGuides {
+    #[default]
+    Off,
+    /// Draw the guides as solid lines (`│`)
+    Solid,
+    /// Draw the guides as dashed lines (`┊`)
+    Dashed,
+}
+
+impl IndentGuides {
+    pub fn glyph(self) -> Option<char> {
+        match self {
+            IndentGuides::Off => None,
+            IndentGuides::Solid => Some('│'),
+            IndentGuides::Dashed => Some('┊'),
+        }
+    }
+}
+
+/// Replaces the spaces of `text` at the columns of indentation guides, i.e.
+/// at multiples of `width` before `indentation`. `text` starts at column
+/// `start` and has no tabs.
+pub fn draw_guides(
+    text: &str,
+    start: usize,
+    indentation: usize,
+    width: usize,
+    glyph: char,
+) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(index, c)| {
+            let column = start + index;
+            if c == ' ' && column < indentation && column.is_multiple_of(width) {
+                glyph
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Finds whitespace errors in a line, like git does: trailing whitespace, and
+/// indentation which doesn't follow the style of the input. That's a tab in
+/// inputs indented with spaces, and a space before a tab in inputs indented
+/// with tabs (spaces after the tabs align the line). If the style is unknown,
+/// indentation with both tabs and spaces is an error.
+///
+/// Returns the byte ranges of the errors, sorted by their position.
+pub fn whitespace_errors(line: &str, style: Option<IndentStyle>) -> Vec<Range<usize>> {
+    let content = split_line_ending(line).0;
+    let end = content.trim_end_matches([' ', '\t']).len();
+    let indentation = &content[..content.len() - content.trim_start_matches([' ', '\t']).len()];
+    // The indentation of blank lines is trailing whitespace.
+    let indentation = &indentation[..indentation.len().min(end)];
+
+    let mut errors = vec![];
+    match style {
+        Some(IndentStyle::Spaces) => errors.extend(runs(indentation, '\t')),
+        Some(IndentStyle::Tabs) => {
+            if let Some(last_tab) = indentation.rfind('\t') {
+                errors.extend(runs(&indentation[..last_tab], ' '));
+            }
+        }
+        None if indentation.contains(' ') && indentation.contains('\t') => {
+            errors.push(0..indentation.len())
+        }
+        None => {}
+    }
+
+    if end < content.len() {
+        errors.push(end..content.len());
+    }
+    errors
+}
+
+/// The byte ranges of the runs of `c` in `text`.
+fn runs(text: &str, c: char) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = vec![];
+    for (index, _) in text.match_indices(c) {
+        match runs.last_mut() {
+            Some(run) if run.end == index => run.end += c.len_utf8(),
+            _ => runs.push(index..index + c.len_utf8()),
+        }
+    }
+    runs
+}
+
+#[test]
+fn test_detect() {
+    assert_eq!(None, IndentStyle::detect("a\nb\n"));
+    assert_eq!(
+        Some(IndentStyle::Spaces),
+        IndentStyle::detect("fn a() {\n    b();\n\tc();\n    d();\n}\n")
+    );
+    assert_eq!(
+        Some(IndentStyle::Tabs),
+        IndentStyle::detect("/*\n * a\n */\nint a() {\n\treturn 0;\n}\n")
+    );
+}
+
+#[test]
+fn test_detect_indent_width() {
+    assert_eq!(None, detect_indent_width("a\nb\n"));
+    assert_eq!(
+        Some(2),
+        detect_indent_width("a:\n  b:\n    c: 1\n\n    d: 2\ne: 3\n")
+    );
+    assert_eq!(
+        Some(4),
+        detect_indent_width("def a():\n    if b:\n        c()\n    d()\n    if e:\n        f()\n")
+    );
+}
+
+#[test]
+fn test_draw_guides() {
+    assert_eq!("│   │   x", draw_guides("        x", 0, 8, 4, '│'));
+    // The text of a region in the middle of the indentation
+    assert_eq!("  ┊ ", draw_guides("    ", 2, 8, 4, '┊'));
+}
+
+#[test]
+fn test_whitespace_errors() {
+    assert!(whitespace_errors("    a = 1;\n", Some(IndentStyle::Spaces)).is_empty());
+    assert_eq!(
+        vec![5..7],
+        whitespace_errors("a = 1  \r\n", Some(IndentStyle::Spaces))
+    );
+    assert_eq!(vec![0..3], whitespace_errors(" \t \n", None));
+
+    assert_eq!(
+        vec![2..3],
+        whitespace_errors("  \t  a\n", Some(IndentStyle::Spaces))
+    );
+    // Spaces after tabs are fine, spaces between them aren't.
+    assert!(whitespace_errors("\t\t  a\n", Some(IndentStyle::Tabs)).is_empty());
+    assert_eq!(
+        vec![1..3],
+        whitespace_errors("\t  \ta\n", Some(IndentStyle::Tabs))
+    );
+    assert_eq!(vec![0..4], whitespace_errors("\t  \ta\n", None));
+}